@@ -0,0 +1,225 @@
+//! An arena of the nodes of an AST, where each node has a `NodeId` with its parent and span, so
+//! that analyses could keep their results in maps keyed by `NodeId`, and walk up from a node to the
+//! nodes enclosing it.
+
+use std::{collections::HashMap, mem::Discriminant, ops::Range};
+
+use crate::{
+    ast::{Arg, Arm, Attribute, Block, Expr, Ident, Operation, Pattern, Spanned, TypeExpr},
+    visit::{
+        walk_arg, walk_arm, walk_attribute, walk_block, walk_expr, walk_operation, walk_pattern,
+        walk_type_expr, Visitor,
+    },
+};
+
+/// The index of a node in an `Arena`, which is stable for the lifetime of the arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// A node of the AST borrowed by an `Arena`.
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub enum NodeRef<'a> {
+    Block(&'a Block),
+    Expr(&'a Expr),
+    Operation(&'a Operation),
+    Pattern(&'a Pattern),
+    TypeExpr(&'a TypeExpr),
+    Arg(&'a Arg),
+    Attribute(&'a Attribute),
+    Arm(&'a Arm),
+    Ident(&'a Ident),
+}
+
+impl NodeRef<'_> {
+    /// The address of the node with its kind, since a node could share the address of the first
+    /// node inside it.
+    fn key(&self) -> (usize, Discriminant<Self>) {
+        let address = match *self {
+            NodeRef::Block(n) => n as *const _ as usize,
+            NodeRef::Expr(n) => n as *const _ as usize,
+            NodeRef::Operation(n) => n as *const _ as usize,
+            NodeRef::Pattern(n) => n as *const _ as usize,
+            NodeRef::TypeExpr(n) => n as *const _ as usize,
+            NodeRef::Arg(n) => n as *const _ as usize,
+            NodeRef::Attribute(n) => n as *const _ as usize,
+            NodeRef::Arm(n) => n as *const _ as usize,
+            NodeRef::Ident(n) => n as *const _ as usize,
+        };
+        (address, std::mem::discriminant(self))
+    }
+}
+
+impl Spanned for NodeRef<'_> {
+    fn span(&self) -> Range<usize> {
+        match self {
+            NodeRef::Block(n) => n.span(),
+            NodeRef::Expr(n) => n.span(),
+            NodeRef::Operation(n) => n.span(),
+            NodeRef::Pattern(n) => n.span(),
+            NodeRef::TypeExpr(n) => n.span(),
+            NodeRef::Arg(n) => n.span(),
+            NodeRef::Attribute(n) => n.span(),
+            NodeRef::Arm(n) => n.span(),
+            NodeRef::Ident(n) => n.span(),
+        }
+    }
+}
+
+/// A node in an `Arena`, with the nodes directly inside it in the order they are written.
+#[derive(Debug, Clone)]
+pub struct Node<'a> {
+    pub value: NodeRef<'a>,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+    pub span: Range<usize>,
+}
+
+/// The nodes of an AST, numbered in the order they are visited from the root block.
+#[derive(Debug, Clone)]
+pub struct Arena<'a> {
+    nodes: Vec<Node<'a>>,
+    ids: HashMap<(usize, Discriminant<NodeRef<'a>>), NodeId>,
+}
+
+impl<'a> Arena<'a> {
+    pub fn new(block: &'a Block) -> Self {
+        let mut builder = Builder {
+            arena: Arena {
+                nodes: Vec::new(),
+                ids: HashMap::new(),
+            },
+            parents: Vec::new(),
+        };
+        builder.visit_block(block);
+        builder.arena
+    }
+
+    /// The block the arena is built from.
+    pub fn root(&self) -> NodeId {
+        NodeId(0)
+    }
+
+    pub fn get(&self, id: NodeId) -> &Node<'a> {
+        &self.nodes[id.0]
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.get(id).parent
+    }
+
+    /// The nodes enclosing the node, from its parent to the root.
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_, 'a> {
+        Ancestors {
+            arena: self,
+            next: self.parent(id),
+        }
+    }
+
+    /// The id of a node of the AST the arena is built from.
+    pub fn id_of(&self, node: NodeRef<'a>) -> Option<NodeId> {
+        self.ids.get(&node.key()).copied()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node<'a>)> {
+        self.nodes.iter().enumerate().map(|(i, n)| (NodeId(i), n))
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<'a> std::ops::Index<NodeId> for Arena<'a> {
+    type Output = Node<'a>;
+
+    fn index(&self, id: NodeId) -> &Node<'a> {
+        self.get(id)
+    }
+}
+
+/// The iterator returned by `Arena::ancestors`.
+#[derive(Debug, Clone)]
+pub struct Ancestors<'b, 'a> {
+    arena: &'b Arena<'a>,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_, '_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let id = self.next?;
+        self.next = self.arena.parent(id);
+        Some(id)
+    }
+}
+
+struct Builder<'a> {
+    arena: Arena<'a>,
+    parents: Vec<NodeId>,
+}
+
+impl<'a> Builder<'a> {
+    fn enter(&mut self, value: NodeRef<'a>, walk: impl FnOnce(&mut Self)) {
+        let id = NodeId(self.arena.nodes.len());
+        let parent = self.parents.last().copied();
+        if let Some(parent) = parent {
+            self.arena.nodes[parent.0].children.push(id);
+        }
+        self.arena.nodes.push(Node {
+            value,
+            parent,
+            children: Vec::new(),
+            span: value.span(),
+        });
+        self.arena.ids.insert(value.key(), id);
+        self.parents.push(id);
+        walk(self);
+        self.parents.pop();
+    }
+}
+
+impl<'a> Visitor<'a> for Builder<'a> {
+    fn visit_block(&mut self, block: &'a Block) {
+        self.enter(NodeRef::Block(block), |b| walk_block(b, block));
+    }
+
+    fn visit_expr(&mut self, e: &'a Expr) {
+        self.enter(NodeRef::Expr(e), |b| walk_expr(b, e));
+    }
+
+    fn visit_operation(&mut self, o: &'a Operation) {
+        self.enter(NodeRef::Operation(o), |b| walk_operation(b, o));
+    }
+
+    fn visit_pattern(&mut self, p: &'a Pattern) {
+        self.enter(NodeRef::Pattern(p), |b| walk_pattern(b, p));
+    }
+
+    fn visit_type_expr(&mut self, t: &'a TypeExpr) {
+        self.enter(NodeRef::TypeExpr(t), |b| walk_type_expr(b, t));
+    }
+
+    fn visit_arg(&mut self, arg: &'a Arg) {
+        self.enter(NodeRef::Arg(arg), |b| walk_arg(b, arg));
+    }
+
+    fn visit_attribute(&mut self, attribute: &'a Attribute) {
+        self.enter(NodeRef::Attribute(attribute), |b| {
+            walk_attribute(b, attribute)
+        });
+    }
+
+    fn visit_arm(&mut self, arm: &'a Arm) {
+        self.enter(NodeRef::Arm(arm), |b| walk_arm(b, arm));
+    }
+
+    fn visit_ident(&mut self, ident: &'a Ident) {
+        self.enter(NodeRef::Ident(ident), |_| {});
+    }
+}
@@ -3,25 +3,191 @@ use std::{
     ops::Range,
 };
 
+/// The lines of a block, with the range the block is written in, including its brackets if any.
 #[derive(Clone)]
-pub struct Block(pub Vec<Expr>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Block {
+    pub lines: Vec<Expr>,
+    pub range: Range<usize>,
+}
 
 impl Debug for Block {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_list().entries(&self.0).finish()
+        f.debug_list().entries(&self.lines).finish()
+    }
+}
+
+/// A node written in a range of characters in the source.
+pub trait Spanned {
+    /// The range of characters the whole node is written in.
+    fn span(&self) -> Range<usize>;
+}
+
+/// The range covering both `a` and `b`.
+fn join(a: Range<usize>, b: Range<usize>) -> Range<usize> {
+    a.start.min(b.start)..a.end.max(b.end)
+}
+
+impl Spanned for Block {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
     }
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Expr {
     LitNum(f64, Range<usize>),
     LitStr(String, Range<usize>),
     LastLine(Range<usize>),
     Var(VarDef),
-    Block(Block),
+    Block(Block, Range<usize>),
+    Array(Vec<Expr>, Range<usize>),
+    Tuple(Vec<Expr>, Range<usize>),
+    Record(Option<Ident>, Vec<Field>, Range<usize>),
+    Paren(Box<Expr>, Range<usize>),
+    Spread(Box<Expr>, Range<usize>),
     Operation(Box<Operation>),
-    Call(Ident, Vec<Expr>),
-    Func(Box<(Vec<VarDef>, Option<Ident>, Expr)>),
+    Call(Ident, Vec<Arg>, Range<usize>),
+    Member(Box<Member>, Range<usize>),
+    Func(Box<Func>, Range<usize>),
+    Struct(Box<StructDecl>),
+    Enum(Box<EnumDecl>),
+    TypeAlias(Box<TypeAlias>),
+    Match(Box<Match>),
+    Break(Option<Ident>, Option<Box<Expr>>, Range<usize>),
+    Continue(Option<Ident>, Range<usize>),
+    Return(Option<Box<Expr>>, Range<usize>),
+    /// Throws the value to the nearest enclosing `!!` catching it.
+    Throw(Box<Expr>, Range<usize>),
+    Let(Box<Let>, Range<usize>),
+    /// The module at the path, loaded as a record of its exports.
+    Import(String, Range<usize>),
+    Export(Box<Expr>, Range<usize>),
+    Attributed(Vec<Attribute>, Box<Expr>),
+    /// A placeholder for a line that could not be parsed, with the syntax error found in it and the
+    /// range of the error, followed by the range of the whole line.
+    Error(String, Range<usize>, Range<usize>),
+}
+
+impl Expr {
+    /// The attributes attached to this expression.
+    pub fn attributes(&self) -> &[Attribute] {
+        match self {
+            Expr::Attributed(attributes, _) => attributes,
+            _ => &[],
+        }
+    }
+
+    /// The attribute of the name attached to this expression.
+    pub fn attribute(&self, name: &str) -> Option<&Attribute> {
+        self.attributes().iter().find(|a| a.name() == name)
+    }
+
+    /// The doc comments written before this expression, one line each.
+    pub fn docs(&self) -> Option<String> {
+        let docs = self
+            .attributes()
+            .iter()
+            .filter(|a| a.name() == "doc")
+            .filter_map(|a| match a.args.first() {
+                Some(Expr::LitStr(doc, _)) => Some(doc.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if docs.is_empty() {
+            None
+        } else {
+            Some(docs.join("\n"))
+        }
+    }
+
+    /// This expression without its attributes.
+    pub fn unattributed(&self) -> &Expr {
+        match self {
+            Expr::Attributed(_, e) => e,
+            e => e,
+        }
+    }
+
+    /// The expressions directly inside this expression.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::LitNum(..)
+            | Expr::LitStr(..)
+            | Expr::LastLine(_)
+            | Expr::Var(_)
+            | Expr::Struct(_)
+            | Expr::Enum(_)
+            | Expr::TypeAlias(_)
+            | Expr::Continue(..)
+            | Expr::Import(..)
+            | Expr::Error(..) => Vec::new(),
+            Expr::Block(Block { lines: items, .. }, _)
+            | Expr::Array(items, _)
+            | Expr::Tuple(items, _) => items.iter().collect(),
+            Expr::Call(_, args, _) => args.iter().map(Arg::value).collect(),
+            Expr::Member(member, _) => Some(&member.receiver)
+                .into_iter()
+                .chain(member.args.iter().flatten().map(Arg::value))
+                .collect(),
+            Expr::Record(_, fields, _) => fields.iter().filter_map(|f| f.value.as_ref()).collect(),
+            Expr::Paren(e, _) | Expr::Spread(e, _) | Expr::Throw(e, _) | Expr::Export(e, _) => {
+                vec![e]
+            }
+            Expr::Let(decl, _) => vec![&decl.value],
+            Expr::Func(func, _) => vec![&func.body],
+            Expr::Attributed(attributes, e) => attributes
+                .iter()
+                .flat_map(|a| &a.args)
+                .chain(Some(&**e))
+                .collect(),
+            Expr::Match(m) => Some(&m.value)
+                .into_iter()
+                .chain(
+                    m.arms
+                        .iter()
+                        .flat_map(|arm| arm.guard.iter().chain(Some(&arm.body))),
+                )
+                .collect(),
+            Expr::Break(_, value, _) | Expr::Return(value, _) => {
+                value.iter().map(|e| &**e).collect()
+            }
+            Expr::Operation(o) => match o.as_ref() {
+                Operation::IfElse(c, b, e) => vec![c, b, e],
+                Operation::Assign(_, r) => vec![r],
+                Operation::Catch(l, _, r) => vec![l, r],
+                Operation::ForEach(_, i, b, _) | Operation::ForEachGenerator(_, i, b, _) => {
+                    vec![i, b]
+                }
+                Operation::Range(l, r, step) | Operation::RangeInclusive(l, r, step) => Some(l)
+                    .into_iter()
+                    .chain(Some(r))
+                    .chain(step.as_ref())
+                    .collect(),
+                Operation::If(l, r)
+                | Operation::While(l, r, _)
+                | Operation::Generator(l, r, _)
+                | Operation::Add(l, r)
+                | Operation::Sub(l, r)
+                | Operation::Mul(l, r)
+                | Operation::Div(l, r)
+                | Operation::BitAnd(l, r)
+                | Operation::BitOr(l, r)
+                | Operation::EQ(l, r)
+                | Operation::NE(l, r)
+                | Operation::GT(l, r)
+                | Operation::LT(l, r)
+                | Operation::GE(l, r)
+                | Operation::LE(l, r)
+                | Operation::And(l, r)
+                | Operation::Or(l, r)
+                | Operation::Default(l, r) => vec![l, r],
+            },
+        }
+    }
 }
 
 impl std::fmt::Debug for Expr {
@@ -31,9 +197,54 @@ impl std::fmt::Debug for Expr {
             Expr::LitStr(s, _) => Debug::fmt(s, f),
             Expr::LastLine(_) => Display::fmt("@", f),
             Expr::Var(v) => Debug::fmt(v, f),
-            Expr::Block(Block(exprs)) => f.debug_list().entries(exprs).finish(),
+            Expr::Block(Block { lines, .. }, _) => f.debug_list().entries(lines).finish(),
+            Expr::Array(elements, _) => {
+                Display::fmt("{", f)?;
+                for (i, element) in elements.iter().enumerate() {
+                    Debug::fmt(element, f)?;
+                    if i < elements.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    } else if elements.len() == 1 {
+                        Display::fmt(",", f)?;
+                    }
+                }
+                Display::fmt("}", f)?;
+                Ok(())
+            }
+            Expr::Tuple(elements, _) => {
+                Display::fmt("(", f)?;
+                for (i, element) in elements.iter().enumerate() {
+                    Debug::fmt(element, f)?;
+                    if i < elements.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    } else if elements.len() == 1 {
+                        Display::fmt(",", f)?;
+                    }
+                }
+                Display::fmt(")", f)?;
+                Ok(())
+            }
+            Expr::Record(name, fields, _) => {
+                if let Some(name) = name {
+                    Debug::fmt(name, f)?;
+                }
+                Display::fmt(".{", f)?;
+                for (i, field) in fields.iter().enumerate() {
+                    Debug::fmt(field, f)?;
+                    if i < fields.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    }
+                }
+                Display::fmt("}", f)?;
+                Ok(())
+            }
+            Expr::Paren(e, _) => Debug::fmt(e, f),
+            Expr::Spread(e, _) => {
+                Display::fmt("...", f)?;
+                Debug::fmt(e, f)
+            }
             Expr::Operation(o) => Debug::fmt(o.as_ref(), f),
-            Expr::Call(Ident(name, _), args) => {
+            Expr::Call(Ident { name, .. }, args, _) => {
                 Display::fmt(name, f)?;
                 Display::fmt("(", f)?;
                 for (i, arg) in args.iter().enumerate() {
@@ -45,24 +256,130 @@ impl std::fmt::Debug for Expr {
                 Display::fmt(")", f)?;
                 Ok(())
             }
-            Expr::Func(func) => {
-                let (args, ret, body) = func.as_ref();
+            Expr::Member(member, _) => Debug::fmt(member.as_ref(), f),
+            Expr::Func(func, _) => {
+                let Func {
+                    params,
+                    ret_type,
+                    body,
+                    ret_doc,
+                } = func.as_ref();
                 Display::fmt("(", f)?;
-                for (i, arg) in args.iter().enumerate() {
-                    Debug::fmt(arg, f)?;
-                    if i < args.len() - 1 {
+                for (i, param) in params.iter().enumerate() {
+                    Debug::fmt(param, f)?;
+                    if i < params.len() - 1 {
                         Display::fmt(", ", f)?;
                     }
                 }
                 Display::fmt(")", f)?;
-                if let Some(Ident(ret, _)) = ret {
+                if let Some(ret) = ret_type {
                     Display::fmt(": ", f)?;
-                    Debug::fmt(ret, f)?;
+                    if let TypeExpr::Func(..) = ret {
+                        Display::fmt("(", f)?;
+                        Debug::fmt(ret, f)?;
+                        Display::fmt(")", f)?;
+                    } else {
+                        Debug::fmt(ret, f)?;
+                    }
+                }
+                if let Some(doc) = ret_doc {
+                    Display::fmt(" #[doc(", f)?;
+                    Debug::fmt(doc, f)?;
+                    Display::fmt(")]", f)?;
                 }
                 Display::fmt(" => ", f)?;
                 Debug::fmt(body, f)?;
                 Ok(())
             }
+            Expr::Struct(s) => Debug::fmt(s.as_ref(), f),
+            Expr::Enum(e) => Debug::fmt(e.as_ref(), f),
+            Expr::TypeAlias(a) => Debug::fmt(a.as_ref(), f),
+            Expr::Match(m) => Debug::fmt(m.as_ref(), f),
+            Expr::Break(label, value, _) => {
+                Display::fmt("break", f)?;
+                if let Some(label) = label {
+                    Display::fmt(" '", f)?;
+                    Debug::fmt(label, f)?;
+                }
+                if let Some(value) = value {
+                    Display::fmt(" ", f)?;
+                    Debug::fmt(value, f)?;
+                }
+                Ok(())
+            }
+            Expr::Continue(label, _) => {
+                Display::fmt("continue", f)?;
+                if let Some(label) = label {
+                    Display::fmt(" '", f)?;
+                    Debug::fmt(label, f)?;
+                }
+                Ok(())
+            }
+            Expr::Return(value, _) => {
+                Display::fmt("return", f)?;
+                if let Some(value) = value {
+                    Display::fmt(" ", f)?;
+                    Debug::fmt(value, f)?;
+                }
+                Ok(())
+            }
+            Expr::Throw(value, _) => {
+                Display::fmt("throw ", f)?;
+                Debug::fmt(value, f)
+            }
+            Expr::Import(path, _) => {
+                Display::fmt("import ", f)?;
+                Debug::fmt(path, f)
+            }
+            Expr::Let(decl, _) => Debug::fmt(decl.as_ref(), f),
+            Expr::Export(e, _) => {
+                Display::fmt("export ", f)?;
+                Debug::fmt(e, f)
+            }
+            Expr::Error(..) => Display::fmt("<error>", f),
+            Expr::Attributed(attributes, e) => {
+                for attribute in attributes {
+                    Debug::fmt(attribute, f)?;
+                    Display::fmt(" ", f)?;
+                }
+                Debug::fmt(e, f)
+            }
+        }
+    }
+}
+
+impl Spanned for Expr {
+    fn span(&self) -> Range<usize> {
+        match self {
+            Expr::LitNum(_, range)
+            | Expr::LitStr(_, range)
+            | Expr::LastLine(range)
+            | Expr::Block(_, range)
+            | Expr::Array(_, range)
+            | Expr::Tuple(_, range)
+            | Expr::Record(_, _, range)
+            | Expr::Paren(_, range)
+            | Expr::Spread(_, range)
+            | Expr::Call(_, _, range)
+            | Expr::Member(_, range)
+            | Expr::Func(_, range)
+            | Expr::Break(_, _, range)
+            | Expr::Continue(_, range)
+            | Expr::Return(_, range)
+            | Expr::Throw(_, range)
+            | Expr::Let(_, range)
+            | Expr::Import(_, range)
+            | Expr::Export(_, range)
+            | Expr::Error(_, _, range) => range.clone(),
+            Expr::Var(v) => v.span(),
+            Expr::Operation(o) => o.span(),
+            Expr::Struct(decl) => decl.range.clone(),
+            Expr::Enum(decl) => decl.range.clone(),
+            Expr::TypeAlias(alias) => alias.range.clone(),
+            Expr::Match(m) => m.range.clone(),
+            Expr::Attributed(attributes, e) => {
+                attributes.iter().map(Spanned::span).fold(e.span(), join)
+            }
         }
     }
 }
@@ -74,10 +391,15 @@ impl Display for Expr {
 }
 
 #[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum Operation {
     If(Expr, Expr),
     IfElse(Expr, Expr, Expr),
-    While(Expr, Expr),
+    While(Expr, Expr, Option<Ident>),
+    Generator(Expr, Expr, Option<Ident>),
+    ForEach(Pattern, Expr, Expr, Option<Ident>),
+    ForEachGenerator(Pattern, Expr, Expr, Option<Ident>),
     Add(Expr, Expr),
     Sub(Expr, Expr),
     Mul(Expr, Expr),
@@ -92,7 +414,100 @@ pub enum Operation {
     LE(Expr, Expr),
     And(Expr, Expr),
     Or(Expr, Expr),
-    Assign(Expr, Expr),
+    /// `l ?? r`, which results in `r` if `l` is `()`, or `l` otherwise without evaluating `r`.
+    Default(Expr, Expr),
+    Assign(Pattern, Expr),
+    /// `body !! pattern ? handler`, which results in the handler if a value matching the pattern is
+    /// thrown while evaluating the body.
+    Catch(Expr, Pattern, Expr),
+    /// A range with an optional step.
+    Range(Expr, Expr, Option<Expr>),
+    RangeInclusive(Expr, Expr, Option<Expr>),
+}
+
+impl Operation {
+    /// The operator the operation is written with.
+    pub fn operator(&self) -> &'static str {
+        match self {
+            Operation::If(..) | Operation::IfElse(..) => "?",
+            Operation::While(..) | Operation::ForEach(..) => "^",
+            Operation::Generator(..) | Operation::ForEachGenerator(..) => "^^",
+            Operation::Add(..) => "+",
+            Operation::Sub(..) => "-",
+            Operation::Mul(..) => "*",
+            Operation::Div(..) => "/",
+            Operation::BitAnd(..) => "&",
+            Operation::BitOr(..) => "|",
+            Operation::EQ(..) => "==",
+            Operation::NE(..) => "!=",
+            Operation::GT(..) => ">",
+            Operation::LT(..) => "<",
+            Operation::GE(..) => ">=",
+            Operation::LE(..) => "<=",
+            Operation::And(..) => "&&",
+            Operation::Or(..) => "||",
+            Operation::Default(..) => "??",
+            Operation::Assign(..) => "=",
+            Operation::Catch(..) => "!!",
+            Operation::Range(..) => "..",
+            Operation::RangeInclusive(..) => "..=",
+        }
+    }
+
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Operation::EQ(..)
+                | Operation::NE(..)
+                | Operation::GT(..)
+                | Operation::LT(..)
+                | Operation::GE(..)
+                | Operation::LE(..)
+        )
+    }
+}
+
+impl Spanned for Operation {
+    fn span(&self) -> Range<usize> {
+        let span = match self {
+            Operation::If(l, r)
+            | Operation::Add(l, r)
+            | Operation::Sub(l, r)
+            | Operation::Mul(l, r)
+            | Operation::Div(l, r)
+            | Operation::BitAnd(l, r)
+            | Operation::BitOr(l, r)
+            | Operation::EQ(l, r)
+            | Operation::NE(l, r)
+            | Operation::GT(l, r)
+            | Operation::LT(l, r)
+            | Operation::GE(l, r)
+            | Operation::LE(l, r)
+            | Operation::And(l, r)
+            | Operation::Or(l, r)
+            | Operation::Default(l, r)
+            | Operation::While(l, r, _)
+            | Operation::Generator(l, r, _) => join(l.span(), r.span()),
+            Operation::IfElse(c, _, e) => join(c.span(), e.span()),
+            Operation::ForEach(p, _, b, _) | Operation::ForEachGenerator(p, _, b, _) => {
+                join(p.span(), b.span())
+            }
+            Operation::Assign(p, r) => join(p.span(), r.span()),
+            Operation::Catch(l, _, r) => join(l.span(), r.span()),
+            Operation::Range(l, r, step) | Operation::RangeInclusive(l, r, step) => step
+                .iter()
+                .map(Spanned::span)
+                .fold(join(l.span(), r.span()), join),
+        };
+        // A labeled loop starts from its label.
+        match self {
+            Operation::While(_, _, Some(label))
+            | Operation::Generator(_, _, Some(label))
+            | Operation::ForEach(_, _, _, Some(label))
+            | Operation::ForEachGenerator(_, _, _, Some(label)) => join(label.span(), span),
+            _ => span,
+        }
+    }
 }
 
 impl Debug for Operation {
@@ -116,7 +531,12 @@ impl Debug for Operation {
                 Display::fmt(")", f)?;
                 Ok(())
             }
-            Operation::While(c, b) => {
+            Operation::While(c, b, label) => {
+                if let Some(label) = label {
+                    Display::fmt("'", f)?;
+                    Debug::fmt(label, f)?;
+                    Display::fmt(": ", f)?;
+                }
                 Display::fmt("(", f)?;
                 Debug::fmt(c, f)?;
                 Display::fmt("^ ", f)?;
@@ -124,6 +544,54 @@ impl Debug for Operation {
                 Display::fmt(")", f)?;
                 Ok(())
             }
+            Operation::Generator(c, b, label) => {
+                if let Some(label) = label {
+                    Display::fmt("'", f)?;
+                    Debug::fmt(label, f)?;
+                    Display::fmt(": ", f)?;
+                }
+                Display::fmt("(", f)?;
+                Debug::fmt(c, f)?;
+                Display::fmt("^^ ", f)?;
+                Debug::fmt(b, f)?;
+                Display::fmt(")", f)?;
+                Ok(())
+            }
+            Operation::ForEach(p, i, b, label) | Operation::ForEachGenerator(p, i, b, label) => {
+                if let Some(label) = label {
+                    Display::fmt("'", f)?;
+                    Debug::fmt(label, f)?;
+                    Display::fmt(": ", f)?;
+                }
+                Display::fmt("(", f)?;
+                Debug::fmt(p, f)?;
+                Display::fmt(" in ", f)?;
+                Debug::fmt(i, f)?;
+                if let Operation::ForEach(..) = self {
+                    Display::fmt("^ ", f)?;
+                } else {
+                    Display::fmt("^^ ", f)?;
+                }
+                Debug::fmt(b, f)?;
+                Display::fmt(")", f)?;
+                Ok(())
+            }
+            Operation::Range(l, r, step) | Operation::RangeInclusive(l, r, step) => {
+                Display::fmt("(", f)?;
+                Debug::fmt(l, f)?;
+                if let Operation::Range(..) = self {
+                    Display::fmt("..", f)?;
+                } else {
+                    Display::fmt("..=", f)?;
+                }
+                Debug::fmt(r, f)?;
+                if let Some(step) = step {
+                    Display::fmt(" by ", f)?;
+                    Debug::fmt(step, f)?;
+                }
+                Display::fmt(")", f)?;
+                Ok(())
+            }
             Operation::Add(l, r) => {
                 Display::fmt("(", f)?;
                 Debug::fmt(l, f)?;
@@ -167,7 +635,7 @@ impl Debug for Operation {
             Operation::BitOr(l, r) => {
                 Display::fmt("(", f)?;
                 Debug::fmt(l, f)?;
-                Display::fmt(" & ", f)?;
+                Display::fmt(" | ", f)?;
                 Debug::fmt(r, f)?;
                 Display::fmt(")", f)?;
                 Ok(())
@@ -207,7 +675,7 @@ impl Debug for Operation {
             Operation::GE(l, r) => {
                 Display::fmt("(", f)?;
                 Debug::fmt(l, f)?;
-                Display::fmt(" <= ", f)?;
+                Display::fmt(" >= ", f)?;
                 Debug::fmt(r, f)?;
                 Display::fmt(")", f)?;
                 Ok(())
@@ -215,7 +683,7 @@ impl Debug for Operation {
             Operation::LE(l, r) => {
                 Display::fmt("(", f)?;
                 Debug::fmt(l, f)?;
-                Display::fmt(" >= ", f)?;
+                Display::fmt(" <= ", f)?;
                 Debug::fmt(r, f)?;
                 Display::fmt(")", f)?;
                 Ok(())
@@ -236,6 +704,14 @@ impl Debug for Operation {
                 Display::fmt(")", f)?;
                 Ok(())
             }
+            Operation::Default(l, r) => {
+                Display::fmt("(", f)?;
+                Debug::fmt(l, f)?;
+                Display::fmt(" ?? ", f)?;
+                Debug::fmt(r, f)?;
+                Display::fmt(")", f)?;
+                Ok(())
+            }
             Operation::Assign(l, r) => {
                 Display::fmt("(", f)?;
                 Debug::fmt(l, f)?;
@@ -244,29 +720,798 @@ impl Debug for Operation {
                 Display::fmt(")", f)?;
                 Ok(())
             }
+            Operation::Catch(l, p, r) => {
+                Display::fmt("(", f)?;
+                Debug::fmt(l, f)?;
+                Display::fmt(" !! ", f)?;
+                Debug::fmt(p, f)?;
+                Display::fmt("? ", f)?;
+                Debug::fmt(r, f)?;
+                Display::fmt(")", f)?;
+                Ok(())
+            }
         }
     }
 }
 
 #[derive(Clone)]
-pub struct Ident(pub String, pub Range<usize>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Ident {
+    pub name: String,
+    pub range: Range<usize>,
+}
+
+impl Spanned for Ident {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
 
 impl Debug for Ident {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Display::fmt(&self.0, f)
+        Display::fmt(&self.name, f)
     }
 }
 
 #[derive(Clone)]
-pub struct VarDef(pub Ident, pub Option<Ident>);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct VarDef {
+    pub name: Ident,
+    pub ty: Option<TypeExpr>,
+}
+
+impl Spanned for VarDef {
+    fn span(&self) -> Range<usize> {
+        self.ty
+            .iter()
+            .map(Spanned::span)
+            .fold(self.name.span(), join)
+    }
+}
 
 impl Debug for VarDef {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        Debug::fmt(&self.0, f)?;
-        if let Some(Ident(t, _)) = &self.1 {
+        Debug::fmt(&self.name, f)?;
+        if let Some(t) = &self.ty {
+            Display::fmt(": ", f)?;
+            Debug::fmt(t, f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum TypeExpr {
+    Name(Ident),
+    Unit(Range<usize>),
+    Tuple(Vec<TypeExpr>, Range<usize>),
+    Array(Box<TypeExpr>, Range<usize>),
+    Func(Box<(Vec<TypeExpr>, TypeExpr)>, Range<usize>),
+    Generic(Ident, Vec<TypeExpr>, Range<usize>),
+    Optional(Box<TypeExpr>, Range<usize>),
+    /// The type of the values thrown, written as `!T` among the members of a union.
+    Thrown(Box<TypeExpr>, Range<usize>),
+    /// Has at least two members, which is checked when deserialized.
+    Union(#[cfg_attr(feature = "serde", serde(deserialize_with = "union_members"))] Vec<TypeExpr>),
+}
+
+#[cfg(feature = "serde")]
+fn union_members<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Vec<TypeExpr>, D::Error> {
+    let types = <Vec<TypeExpr> as serde::Deserialize>::deserialize(d)?;
+    if types.len() < 2 {
+        return Err(serde::de::Error::invalid_length(
+            types.len(),
+            &"at least two members of a union",
+        ));
+    }
+    Ok(types)
+}
+
+impl Spanned for TypeExpr {
+    fn span(&self) -> Range<usize> {
+        match self {
+            TypeExpr::Name(name) => name.span(),
+            TypeExpr::Unit(range)
+            | TypeExpr::Tuple(_, range)
+            | TypeExpr::Array(_, range)
+            | TypeExpr::Func(_, range)
+            | TypeExpr::Generic(_, _, range)
+            | TypeExpr::Optional(_, range)
+            | TypeExpr::Thrown(_, range) => range.clone(),
+            TypeExpr::Union(types) => match (types.first(), types.last()) {
+                (Some(first), Some(last)) => join(first.span(), last.span()),
+                _ => 0..0,
+            },
+        }
+    }
+}
+
+impl Debug for TypeExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeExpr::Name(name) => Debug::fmt(name, f),
+            TypeExpr::Unit(_) => Display::fmt("()", f),
+            TypeExpr::Tuple(types, _) => {
+                Display::fmt("(", f)?;
+                for (i, t) in types.iter().enumerate() {
+                    Debug::fmt(t, f)?;
+                    if i < types.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    } else if types.len() == 1 {
+                        Display::fmt(",", f)?;
+                    }
+                }
+                Display::fmt(")", f)?;
+                Ok(())
+            }
+            TypeExpr::Array(t, _) => {
+                Display::fmt("{", f)?;
+                Debug::fmt(t, f)?;
+                Display::fmt("}", f)?;
+                Ok(())
+            }
+            TypeExpr::Func(func, _) => {
+                let (args, ret) = func.as_ref();
+                Display::fmt("(", f)?;
+                for (i, arg) in args.iter().enumerate() {
+                    Debug::fmt(arg, f)?;
+                    if i < args.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    }
+                }
+                Display::fmt(") => ", f)?;
+                Debug::fmt(ret, f)?;
+                Ok(())
+            }
+            TypeExpr::Generic(name, args, _) => {
+                Debug::fmt(name, f)?;
+                Display::fmt("<", f)?;
+                for (i, arg) in args.iter().enumerate() {
+                    Debug::fmt(arg, f)?;
+                    if i < args.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    }
+                }
+                Display::fmt(">", f)?;
+                Ok(())
+            }
+            TypeExpr::Optional(t, _) | TypeExpr::Thrown(t, _) => {
+                let prefix = if let TypeExpr::Optional(..) = self {
+                    "?"
+                } else {
+                    "!"
+                };
+                Display::fmt(prefix, f)?;
+                if let TypeExpr::Union(_) | TypeExpr::Func(..) = t.as_ref() {
+                    Display::fmt("(", f)?;
+                    Debug::fmt(t, f)?;
+                    Display::fmt(")", f)
+                } else {
+                    Debug::fmt(t, f)
+                }
+            }
+            TypeExpr::Union(types) => {
+                for (i, t) in types.iter().enumerate() {
+                    if let TypeExpr::Func(..) = t {
+                        Display::fmt("(", f)?;
+                        Debug::fmt(t, f)?;
+                        Display::fmt(")", f)?;
+                    } else {
+                        Debug::fmt(t, f)?;
+                    }
+                    if i < types.len() - 1 {
+                        Display::fmt(" | ", f)?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Field {
+    pub name: Ident,
+    /// Written as the shorthand `name` for a variable of the same name if `None`.
+    pub value: Option<Expr>,
+}
+
+impl Spanned for Field {
+    fn span(&self) -> Range<usize> {
+        self.value
+            .iter()
+            .map(Spanned::span)
+            .fold(self.name.span(), join)
+    }
+}
+
+impl Debug for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.name, f)?;
+        if let Some(value) = &self.value {
             Display::fmt(": ", f)?;
-            Display::fmt(t, f)?;
+            Debug::fmt(value, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// An argument of a function call.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Arg {
+    Positional(Expr),
+    /// An argument given to the parameter of the name, written as `name: value`.
+    Named(Ident, Expr),
+    /// The items of an array or a tuple given as positional arguments, written as `...args`.
+    Spread(Expr, Range<usize>),
+}
+
+impl Arg {
+    /// The expression the argument is given by.
+    pub fn value(&self) -> &Expr {
+        match self {
+            Arg::Positional(e) | Arg::Named(_, e) | Arg::Spread(e, _) => e,
+        }
+    }
+}
+
+impl Spanned for Arg {
+    fn span(&self) -> Range<usize> {
+        match self {
+            Arg::Positional(e) => e.span(),
+            Arg::Named(name, e) => join(name.span(), e.span()),
+            Arg::Spread(_, range) => range.clone(),
+        }
+    }
+}
+
+impl Debug for Arg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arg::Positional(e) => Debug::fmt(e, f),
+            Arg::Named(name, e) => {
+                Debug::fmt(name, f)?;
+                Display::fmt(": ", f)?;
+                Debug::fmt(e, f)
+            }
+            Arg::Spread(e, _) => {
+                Display::fmt("...", f)?;
+                Debug::fmt(e, f)
+            }
+        }
+    }
+}
+
+/// A declaration of the variables bound by a pattern, written as `let pattern = value`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Let {
+    pub pattern: Pattern,
+    pub value: Expr,
+    /// Written as `const pattern = value`, whose variables could not be assigned again.
+    pub constant: bool,
+}
+
+impl Debug for Let {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt(if self.constant { "(const " } else { "(let " }, f)?;
+        Debug::fmt(&self.pattern, f)?;
+        Display::fmt(" = ", f)?;
+        Debug::fmt(&self.value, f)?;
+        Display::fmt(")", f)
+    }
+}
+
+/// A member of a value, written as `value.name`, which is called if followed by arguments as
+/// `value.name(args)`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Member {
+    pub receiver: Expr,
+    pub name: Ident,
+    pub args: Option<Vec<Arg>>,
+    /// Accessed by `?.` instead of `.`, which results in `()` without accessing the member if the
+    /// value is `()`.
+    pub safe: bool,
+}
+
+impl Debug for Member {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.receiver, f)?;
+        Display::fmt(if self.safe { "?." } else { "." }, f)?;
+        Debug::fmt(&self.name, f)?;
+        if let Some(args) = &self.args {
+            Display::fmt("(", f)?;
+            for (i, arg) in args.iter().enumerate() {
+                Debug::fmt(arg, f)?;
+                if i < args.len() - 1 {
+                    Display::fmt(", ", f)?;
+                }
+            }
+            Display::fmt(")", f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A function with its parameters, return type, body and the doc comment of the returned value.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Func {
+    pub params: Vec<Param>,
+    pub ret_type: Option<TypeExpr>,
+    pub body: Expr,
+    pub ret_doc: Option<String>,
+}
+
+/// A parameter of a function, with its doc comment.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Param {
+    pub pattern: Pattern,
+    pub doc: Option<String>,
+}
+
+impl Spanned for Param {
+    fn span(&self) -> Range<usize> {
+        self.pattern.span()
+    }
+}
+
+impl Debug for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(doc) = &self.doc {
+            Display::fmt("#[doc(", f)?;
+            Debug::fmt(doc, f)?;
+            Display::fmt(")] ", f)?;
+        }
+        Debug::fmt(&self.pattern, f)
+    }
+}
+
+/// Metadata attached to the expression on the line after it, written as `#[name(args)]`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Attribute {
+    pub name: Ident,
+    pub args: Vec<Expr>,
+    pub range: Range<usize>,
+}
+
+impl Attribute {
+    pub fn name(&self) -> &str {
+        &self.name.name
+    }
+}
+
+impl Spanned for Attribute {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Debug for Attribute {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt("#[", f)?;
+        Debug::fmt(&self.name, f)?;
+        if !self.args.is_empty() {
+            Display::fmt("(", f)?;
+            for (i, arg) in self.args.iter().enumerate() {
+                Debug::fmt(arg, f)?;
+                if i < self.args.len() - 1 {
+                    Display::fmt(", ", f)?;
+                }
+            }
+            Display::fmt(")", f)?;
+        }
+        Display::fmt("]", f)
+    }
+}
+
+fn fmt_type_params(params: &[Ident], f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    if !params.is_empty() {
+        Display::fmt("<", f)?;
+        for (i, param) in params.iter().enumerate() {
+            Debug::fmt(param, f)?;
+            if i < params.len() - 1 {
+                Display::fmt(", ", f)?;
+            }
+        }
+        Display::fmt(">", f)?;
+    }
+    Ok(())
+}
+
+/// A nominal record type, with its name, type parameters and fields.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct StructDecl {
+    pub name: Ident,
+    pub type_params: Vec<Ident>,
+    pub fields: Vec<FieldDef>,
+    pub range: Range<usize>,
+}
+
+impl Spanned for StructDecl {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Debug for StructDecl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt("struct ", f)?;
+        Debug::fmt(&self.name, f)?;
+        fmt_type_params(&self.type_params, f)?;
+        Display::fmt(" { ", f)?;
+        for (i, field) in self.fields.iter().enumerate() {
+            Debug::fmt(field, f)?;
+            if i < self.fields.len() - 1 {
+                Display::fmt(", ", f)?;
+            }
+        }
+        Display::fmt(" }", f)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct FieldDef {
+    pub name: Ident,
+    pub ty: TypeExpr,
+}
+
+impl Spanned for FieldDef {
+    fn span(&self) -> Range<usize> {
+        join(self.name.span(), self.ty.span())
+    }
+}
+
+impl Debug for FieldDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.name, f)?;
+        Display::fmt(": ", f)?;
+        Debug::fmt(&self.ty, f)?;
+        Ok(())
+    }
+}
+
+/// A tagged enum type, with its name, type parameters and variants.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct EnumDecl {
+    pub name: Ident,
+    pub type_params: Vec<Ident>,
+    pub variants: Vec<Variant>,
+    pub range: Range<usize>,
+}
+
+impl Spanned for EnumDecl {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Debug for EnumDecl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt("enum ", f)?;
+        Debug::fmt(&self.name, f)?;
+        fmt_type_params(&self.type_params, f)?;
+        Display::fmt(" { ", f)?;
+        for (i, variant) in self.variants.iter().enumerate() {
+            Debug::fmt(variant, f)?;
+            if i < self.variants.len() - 1 {
+                Display::fmt(", ", f)?;
+            }
+        }
+        Display::fmt(" }", f)?;
+        Ok(())
+    }
+}
+
+/// An enum variant with the types of its payload, which is empty for a variant without payload.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Variant {
+    pub name: Ident,
+    pub payload: Vec<TypeExpr>,
+    pub range: Range<usize>,
+}
+
+impl Spanned for Variant {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Debug for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.name, f)?;
+        if !self.payload.is_empty() {
+            Display::fmt("(", f)?;
+            for (i, t) in self.payload.iter().enumerate() {
+                Debug::fmt(t, f)?;
+                if i < self.payload.len() - 1 {
+                    Display::fmt(", ", f)?;
+                }
+            }
+            Display::fmt(")", f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A type alias, with its name, type parameters and the aliased type.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct TypeAlias {
+    pub name: Ident,
+    pub type_params: Vec<Ident>,
+    pub ty: TypeExpr,
+    pub range: Range<usize>,
+}
+
+impl Spanned for TypeAlias {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Debug for TypeAlias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt("type ", f)?;
+        Debug::fmt(&self.name, f)?;
+        fmt_type_params(&self.type_params, f)?;
+        Display::fmt(" = ", f)?;
+        Debug::fmt(&self.ty, f)?;
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum Pattern {
+    Var(VarDef),
+    Wildcard(Range<usize>),
+    Tuple(Vec<Pattern>, Range<usize>),
+    Record(Option<Ident>, Vec<FieldPattern>, Range<usize>),
+    Array(Vec<Pattern>, Range<usize>),
+    /// Binds the rest elements of an array pattern, where `..._` is written for `None`.
+    Rest(Option<Ident>, Range<usize>),
+    LitNum(f64, Range<usize>),
+    LitStr(String, Range<usize>),
+    /// An enum variant with payload patterns. Variants without payload are written as `Var`, which
+    /// are told apart from bindings by the declared variants.
+    Variant(Ident, Vec<Pattern>, Range<usize>),
+}
+
+impl Pattern {
+    /// The variables bound by the pattern.
+    pub fn bindings(&self) -> Vec<&Ident> {
+        fn bindings<'a>(p: &'a Pattern, names: &mut Vec<&'a Ident>) {
+            match p {
+                Pattern::Var(VarDef { name, .. }) | Pattern::Rest(Some(name), _) => {
+                    names.push(name)
+                }
+                Pattern::Tuple(items, _)
+                | Pattern::Array(items, _)
+                | Pattern::Variant(_, items, _) => {
+                    for p in items {
+                        bindings(p, names);
+                    }
+                }
+                Pattern::Record(_, fields, _) => {
+                    for FieldPattern { name, pattern } in fields {
+                        match pattern {
+                            Some(p) => bindings(p, names),
+                            None => names.push(name),
+                        }
+                    }
+                }
+                Pattern::Wildcard(_)
+                | Pattern::Rest(None, _)
+                | Pattern::LitNum(..)
+                | Pattern::LitStr(..) => {}
+            }
+        }
+        let mut names = Vec::new();
+        bindings(self, &mut names);
+        names
+    }
+}
+
+impl Spanned for Pattern {
+    fn span(&self) -> Range<usize> {
+        match self {
+            Pattern::Var(v) => v.span(),
+            Pattern::Wildcard(range)
+            | Pattern::Tuple(_, range)
+            | Pattern::Record(_, _, range)
+            | Pattern::Array(_, range)
+            | Pattern::Rest(_, range)
+            | Pattern::LitNum(_, range)
+            | Pattern::LitStr(_, range)
+            | Pattern::Variant(_, _, range) => range.clone(),
+        }
+    }
+}
+
+impl Debug for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Var(v) => Debug::fmt(v, f),
+            Pattern::Wildcard(_) => Display::fmt("_", f),
+            Pattern::Tuple(elements, _) => {
+                Display::fmt("(", f)?;
+                for (i, element) in elements.iter().enumerate() {
+                    Debug::fmt(element, f)?;
+                    if i < elements.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    } else if elements.len() == 1 {
+                        Display::fmt(",", f)?;
+                    }
+                }
+                Display::fmt(")", f)?;
+                Ok(())
+            }
+            Pattern::Record(name, fields, _) => {
+                if let Some(name) = name {
+                    Debug::fmt(name, f)?;
+                }
+                Display::fmt(".{", f)?;
+                for (i, field) in fields.iter().enumerate() {
+                    Debug::fmt(field, f)?;
+                    if i < fields.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    }
+                }
+                Display::fmt("}", f)?;
+                Ok(())
+            }
+            Pattern::Array(elements, _) => {
+                Display::fmt("{", f)?;
+                for (i, element) in elements.iter().enumerate() {
+                    Debug::fmt(element, f)?;
+                    if i < elements.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    } else if elements.len() == 1 {
+                        Display::fmt(",", f)?;
+                    }
+                }
+                Display::fmt("}", f)?;
+                Ok(())
+            }
+            Pattern::Rest(name, _) => {
+                Display::fmt("...", f)?;
+                match name {
+                    Some(name) => Debug::fmt(name, f),
+                    None => Display::fmt("_", f),
+                }
+            }
+            Pattern::LitNum(n, _) => Debug::fmt(n, f),
+            Pattern::LitStr(s, _) => Debug::fmt(s, f),
+            Pattern::Variant(name, args, _) => {
+                Debug::fmt(name, f)?;
+                Display::fmt("(", f)?;
+                for (i, arg) in args.iter().enumerate() {
+                    Debug::fmt(arg, f)?;
+                    if i < args.len() - 1 {
+                        Display::fmt(", ", f)?;
+                    }
+                }
+                Display::fmt(")", f)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct FieldPattern {
+    pub name: Ident,
+    /// Binds the field to a variable of the same name if `None`.
+    pub pattern: Option<Pattern>,
+}
+
+impl Spanned for FieldPattern {
+    fn span(&self) -> Range<usize> {
+        self.pattern
+            .iter()
+            .map(Spanned::span)
+            .fold(self.name.span(), join)
+    }
+}
+
+impl Debug for FieldPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.name, f)?;
+        if let Some(value) = &self.pattern {
+            Display::fmt(": ", f)?;
+            Debug::fmt(value, f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A `match` expression, with the matched expression and the arms.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Match {
+    pub value: Expr,
+    pub arms: Vec<Arm>,
+    pub range: Range<usize>,
+}
+
+impl Spanned for Match {
+    fn span(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl Debug for Match {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Display::fmt("match ", f)?;
+        Debug::fmt(&self.value, f)?;
+        Display::fmt(" { ", f)?;
+        for (i, arm) in self.arms.iter().enumerate() {
+            Debug::fmt(arm, f)?;
+            if i < self.arms.len() - 1 {
+                Display::fmt(", ", f)?;
+            }
+        }
+        Display::fmt(" }", f)?;
+        Ok(())
+    }
+}
+
+/// A `match` arm, with the pattern, the optional guard and the resulting expression.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Expr,
+}
+
+impl Spanned for Arm {
+    fn span(&self) -> Range<usize> {
+        join(self.pattern.span(), self.body.span())
+    }
+}
+
+impl Debug for Arm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&self.pattern, f)?;
+        if let Some(guard) = &self.guard {
+            Display::fmt(" if ", f)?;
+            Debug::fmt(guard, f)?;
         }
+        Display::fmt(" => ", f)?;
+        Debug::fmt(&self.body, f)?;
         Ok(())
     }
 }
@@ -0,0 +1,844 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::{
+    ast::{
+        Arg, Block, EnumDecl, Expr, Func, Ident, Match, Operation, Pattern, Spanned, TypeExpr,
+        VarDef, Variant,
+    },
+    diagnostic::Diagnostic,
+};
+
+/// Checks a parsed program for errors that are not syntax errors.
+pub fn check(block: &Block) -> Vec<Diagnostic> {
+    let mut exprs = Vec::new();
+    for e in &block.lines {
+        walk(e, &mut exprs);
+    }
+    let mut enums = Enums(HashMap::new());
+    for e in &exprs {
+        if let Expr::Enum(decl) = e {
+            for v in &decl.variants {
+                enums
+                    .0
+                    .entry(v.name.name.as_str())
+                    .or_default()
+                    .push((decl, v));
+            }
+        }
+    }
+    let funcs = funcs(&exprs);
+    let throws = Throws {
+        funcs: &funcs,
+        enums: &enums,
+    };
+    let mut diagnostics = Vec::new();
+    for e in &exprs {
+        match e {
+            Expr::Match(m) => check_exhaustive(m, &enums, &mut diagnostics),
+            Expr::Func(func, _) => throws.check(func, &mut diagnostics),
+            Expr::Call(name, args, range) => {
+                check_arg_order(args, &mut diagnostics);
+                if let Some(Some(func)) = funcs.get(name.name.as_str()) {
+                    check_args(name, args, range, func, &mut diagnostics);
+                }
+            }
+            _ => {}
+        }
+        check_attributes(e, &mut diagnostics);
+        check_bitwise_comparison(e, &mut diagnostics);
+        check_unit_operators(e, &mut diagnostics);
+    }
+    let mut scopes = Scopes(vec![(block.span(), HashMap::new())]);
+    for e in &block.lines {
+        check_assignments(e, &mut scopes, &mut diagnostics);
+    }
+    diagnostics
+}
+
+/// The type of a thrown value by its name, or `None` if it could not be known.
+type Thrown<'a> = Option<&'a str>;
+
+/// Finds the types of the values thrown out of function bodies, known from `throw` and the annotated
+/// return types of the functions called, to check them against the annotated return types.
+struct Throws<'a, 'b> {
+    funcs: &'b HashMap<&'a str, Option<&'a Func>>,
+    enums: &'b Enums<'a>,
+}
+
+impl<'a> Throws<'a, '_> {
+    /// Reports the values thrown out of a function whose annotated return type does not include
+    /// their types.
+    fn check(&self, func: &'a Func, diagnostics: &mut Vec<Diagnostic>) {
+        let ret = match &func.ret_type {
+            Some(ret) => ret,
+            None => return,
+        };
+        let declared = thrown_types(ret);
+        let mut found = Vec::new();
+        self.thrown(&func.body, &mut found);
+        for (thrown, e) in found {
+            let covered = match thrown {
+                Some(name) => declared.iter().any(|d| d.is_none() || *d == Some(name)),
+                None => !declared.is_empty(),
+            };
+            if covered {
+                continue;
+            }
+            let what = match (e, thrown) {
+                (Expr::Call(name, ..), Some(thrown)) => {
+                    format!("`{}` could throw `{}`", name.name, thrown)
+                }
+                (Expr::Call(name, ..), None) => format!("`{}` could throw", name.name),
+                (_, Some(thrown)) => format!("`throw` of `{}`", thrown),
+                (_, None) => "`throw`".to_string(),
+            };
+            diagnostics.push(
+                Diagnostic::new(
+                    format!(
+                        "{} in a function returning `{:?}`, which does not include the type of thrown values such as `{:?} | !{}`.",
+                        what,
+                        ret,
+                        ret,
+                        thrown.unwrap_or("E")
+                    ),
+                    e.span(),
+                )
+                .with_related("The return type is annotated here.", ret.span()),
+            );
+        }
+    }
+
+    /// The values thrown out of `e` and not caught inside it, except by nested functions, each with
+    /// its type and the `throw` or the call throwing it.
+    fn thrown(&self, e: &'a Expr, found: &mut Vec<(Thrown<'a>, &'a Expr)>) {
+        match e {
+            Expr::Func(..) => return,
+            Expr::Throw(value, _) => found.push((self.type_name(value), e)),
+            Expr::Call(name, ..) => {
+                if let Some(Some(Func {
+                    ret_type: Some(ret),
+                    ..
+                })) = self.funcs.get(name.name.as_str())
+                {
+                    found.extend(thrown_types(ret).into_iter().map(|t| (t, e)));
+                }
+            }
+            Expr::Operation(o) => {
+                if let Operation::Catch(body, pattern, handler) = o.as_ref() {
+                    let mut thrown = Vec::new();
+                    self.thrown(body, &mut thrown);
+                    found.extend(thrown.into_iter().filter(|(t, _)| !catches(pattern, *t)));
+                    return self.thrown(handler, found);
+                }
+            }
+            _ => {}
+        }
+        for child in e.children() {
+            self.thrown(child, found);
+        }
+    }
+
+    /// The name of the type of a thrown value, known for typed records and variants of enums.
+    fn type_name(&self, e: &'a Expr) -> Thrown<'a> {
+        match e {
+            Expr::Record(Some(name), ..) => Some(&name.name),
+            Expr::Var(VarDef { name, ty: None }) | Expr::Call(name, ..) => {
+                match self.enums.0.get(name.name.as_str())?.as_slice() {
+                    [(decl, _)] => Some(&decl.name.name),
+                    _ => None,
+                }
+            }
+            Expr::Paren(e, _) => self.type_name(e),
+            _ => None,
+        }
+    }
+}
+
+/// The `!T` members of `t`, by the names of their types.
+fn thrown_types(t: &TypeExpr) -> Vec<Thrown<'_>> {
+    match t {
+        TypeExpr::Thrown(t, _) => vec![match t.as_ref() {
+            TypeExpr::Name(name) | TypeExpr::Generic(name, ..) => Some(&name.name),
+            _ => None,
+        }],
+        TypeExpr::Union(types) => types.iter().flat_map(thrown_types).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Whether a value of the type `thrown` is caught by `pattern`.
+fn catches(pattern: &Pattern, thrown: Thrown) -> bool {
+    match pattern {
+        Pattern::Wildcard(_) | Pattern::Var(VarDef { ty: None, .. }) => true,
+        Pattern::Var(VarDef { ty: Some(t), .. }) => match (t, thrown) {
+            (TypeExpr::Name(name) | TypeExpr::Generic(name, ..), Some(thrown)) => {
+                name.name == thrown
+            }
+            _ => false,
+        },
+        Pattern::Record(Some(name), ..) => Some(name.name.as_str()) == thrown,
+        _ => false,
+    }
+}
+
+/// The variables declared in the enclosing scopes from the outermost, along with whether each is
+/// declared by `const`, and the range of each scope.
+struct Scopes<'a>(Vec<(Range<usize>, Scope<'a>)>);
+
+type Scope<'a> = HashMap<&'a str, (&'a Ident, bool)>;
+
+impl<'a> Scopes<'a> {
+    fn declare(&mut self, pattern: &'a Pattern, immutable: bool) {
+        let (_, scope) = self.0.last_mut().unwrap();
+        for name in pattern.bindings() {
+            scope.insert(&name.name, (name, immutable));
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<(&'a Ident, bool)> {
+        self.0
+            .iter()
+            .rev()
+            .find_map(|(_, scope)| scope.get(name).copied())
+    }
+
+    /// The declared variable whose name `name` is most likely a typo of.
+    fn similar(&self, name: &str) -> Option<&'a Ident> {
+        let max = (name.chars().count() + 1) / 3;
+        self.0
+            .iter()
+            .flat_map(|(_, scope)| scope.values())
+            .map(|(declared, _)| (edit_distance(name, &declared.name), *declared))
+            .filter(|(distance, _)| *distance <= max)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, declared)| declared)
+    }
+
+    /// The range of the innermost scope.
+    fn range(&self) -> Range<usize> {
+        self.0.last().unwrap().0.clone()
+    }
+
+    fn scoped(&mut self, range: Range<usize>, f: impl FnOnce(&mut Self)) {
+        self.0.push((range, HashMap::new()));
+        f(self);
+        self.0.pop();
+    }
+}
+
+/// The number of characters to insert, delete or replace to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let replaced = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// Reports assignments to variables that are not declared by `let` or `const` before, or declared
+/// by `const`. Blocks, functions, match arms, loop bodies and the branches of conditionals have
+/// their own scopes.
+fn check_assignments<'a>(e: &'a Expr, scopes: &mut Scopes<'a>, diagnostics: &mut Vec<Diagnostic>) {
+    match e {
+        Expr::Let(decl, _) => {
+            check_assignments(&decl.value, scopes, diagnostics);
+            scopes.declare(&decl.pattern, decl.constant);
+        }
+        Expr::Block(block, range) => scopes.scoped(range.clone(), |scopes| {
+            for e in &block.lines {
+                check_assignments(e, scopes, diagnostics);
+            }
+        }),
+        Expr::Func(func, range) => scopes.scoped(range.clone(), |scopes| {
+            for param in &func.params {
+                scopes.declare(&param.pattern, false);
+            }
+            check_assignments(&func.body, scopes, diagnostics);
+        }),
+        Expr::Match(m) => {
+            check_assignments(&m.value, scopes, diagnostics);
+            for arm in &m.arms {
+                scopes.scoped(arm.span(), |scopes| {
+                    scopes.declare(&arm.pattern, false);
+                    for e in arm.guard.iter().chain(Some(&arm.body)) {
+                        check_assignments(e, scopes, diagnostics);
+                    }
+                });
+            }
+        }
+        Expr::Operation(o) => match o.as_ref() {
+            Operation::Assign(pattern, value) => {
+                check_assignments(value, scopes, diagnostics);
+                for name in pattern.bindings() {
+                    check_assignment(name, scopes, diagnostics);
+                }
+            }
+            Operation::ForEach(pattern, iterable, body, _)
+            | Operation::ForEachGenerator(pattern, iterable, body, _) => {
+                check_assignments(iterable, scopes, diagnostics);
+                scopes.scoped(o.span(), |scopes| {
+                    scopes.declare(pattern, false);
+                    check_assignments(body, scopes, diagnostics);
+                });
+            }
+            Operation::Catch(body, pattern, handler) => {
+                check_assignments(body, scopes, diagnostics);
+                scopes.scoped(handler.span(), |scopes| {
+                    scopes.declare(pattern, false);
+                    check_assignments(handler, scopes, diagnostics);
+                });
+            }
+            Operation::If(condition, body)
+            | Operation::While(condition, body, _)
+            | Operation::Generator(condition, body, _) => {
+                check_assignments(condition, scopes, diagnostics);
+                check_scoped(body, scopes, diagnostics);
+            }
+            Operation::IfElse(condition, then, otherwise) => {
+                check_assignments(condition, scopes, diagnostics);
+                check_scoped(then, scopes, diagnostics);
+                check_scoped(otherwise, scopes, diagnostics);
+            }
+            _ => {
+                for child in e.children() {
+                    check_assignments(child, scopes, diagnostics);
+                }
+            }
+        },
+        _ => {
+            for child in e.children() {
+                check_assignments(child, scopes, diagnostics);
+            }
+        }
+    }
+}
+
+/// Checks `e` in a scope of its own.
+fn check_scoped<'a>(e: &'a Expr, scopes: &mut Scopes<'a>, diagnostics: &mut Vec<Diagnostic>) {
+    scopes.scoped(e.span(), |scopes| check_assignments(e, scopes, diagnostics));
+}
+
+fn check_assignment(name: &Ident, scopes: &Scopes, diagnostics: &mut Vec<Diagnostic>) {
+    match scopes.get(&name.name) {
+        Some((_, false)) => {}
+        Some((declared, true)) => diagnostics.push(
+            Diagnostic::new(
+                format!("Assignment to the immutable variable `{}`.", name.name),
+                name.range.clone(),
+            )
+            .with_related(
+                format!("`{}` is declared by `const` here.", name.name),
+                declared.range.clone(),
+            ),
+        ),
+        None => {
+            let diagnostic = Diagnostic::new(
+                format!(
+                    "Assignment to the undeclared variable `{}`, declare it by `let` or `const` first.",
+                    name.name
+                ),
+                name.range.clone(),
+            )
+            .with_related(
+                format!("No `{}` is declared in this scope.", name.name),
+                scopes.range(),
+            );
+            diagnostics.push(match scopes.similar(&name.name) {
+                Some(similar) => diagnostic.with_related(
+                    format!(
+                        "A variable of a similar name `{}` is declared here.",
+                        similar.name
+                    ),
+                    similar.range.clone(),
+                ),
+                None => diagnostic,
+            });
+        }
+    }
+}
+
+/// Warns about `??` and `?.` on values which are never `()`, where `??` never evaluates its right
+/// operand and `?.` works as `.`.
+fn check_unit_operators(e: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+    match e {
+        Expr::Operation(o) => {
+            if let Operation::Default(l, _) = o.as_ref() {
+                if never_unit(l) {
+                    diagnostics.push(Diagnostic::warning(
+                        "The left operand of `??` is never `()`, so the right operand is never evaluated.",
+                        l.span(),
+                    ));
+                }
+            }
+        }
+        Expr::Member(m, range) if m.safe && never_unit(&m.receiver) => {
+            diagnostics.push(Diagnostic::warning(
+                "`?.` on a value which is never `()`, use `.` instead.",
+                range.clone(),
+            ));
+        }
+        _ => {}
+    }
+}
+
+/// Whether `e` is known to never result in `()`, such as literals and the results of arithmetic.
+fn never_unit(e: &Expr) -> bool {
+    match e {
+        Expr::LitNum(..)
+        | Expr::LitStr(..)
+        | Expr::Array(..)
+        | Expr::Record(..)
+        | Expr::Func(..) => true,
+        Expr::Tuple(items, _) => !items.is_empty(),
+        Expr::Paren(e, _) => never_unit(e),
+        Expr::Block(block, _) => block.lines.last().is_some_and(never_unit),
+        Expr::Operation(o) => match o.as_ref() {
+            Operation::Add(..)
+            | Operation::Sub(..)
+            | Operation::Mul(..)
+            | Operation::Div(..)
+            | Operation::BitAnd(..)
+            | Operation::BitOr(..)
+            | Operation::And(..)
+            | Operation::Or(..) => true,
+            Operation::IfElse(_, then, otherwise) => never_unit(then) && never_unit(otherwise),
+            Operation::Default(_, r) => never_unit(r),
+            o => o.is_comparison(),
+        },
+        _ => false,
+    }
+}
+
+/// Warns about comparisons as operands of `&` and `|`, which bind looser than comparisons unlike
+/// in many other languages.
+fn check_bitwise_comparison(e: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+    if let Expr::Operation(o) = e {
+        if let Operation::BitAnd(l, r) | Operation::BitOr(l, r) = o.as_ref() {
+            for operand in [l, r] {
+                if let Expr::Operation(c) = operand {
+                    if c.is_comparison() {
+                        diagnostics.push(Diagnostic::warning(
+                            format!(
+                                "`{}` binds tighter than `{}`, add parentheses to make the grouping clear.",
+                                c.operator(),
+                                o.operator()
+                            ),
+                            o.span(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The functions declared or assigned as variables, indexed by the names of the variables. A name
+/// bound more than once or bound to anything but a function is mapped to `None`, as which function
+/// it calls could not be known.
+fn funcs<'a>(exprs: &[&'a Expr]) -> HashMap<&'a str, Option<&'a Func>> {
+    let mut funcs = HashMap::new();
+    for e in exprs {
+        let (pattern, value) = match e {
+            Expr::Let(decl, _) => (&decl.pattern, &decl.value),
+            Expr::Operation(o) => match o.as_ref() {
+                Operation::Assign(pattern, value) => (pattern, value),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        if let Pattern::Var(VarDef {
+            name: Ident { name, .. },
+            ..
+        }) = pattern
+        {
+            let func = match value {
+                Expr::Func(func, _) => Some(func.as_ref()),
+                _ => None,
+            };
+            funcs
+                .entry(name.as_str())
+                .and_modify(|f| *f = None)
+                .or_insert(func);
+        }
+    }
+    funcs
+}
+
+fn check_arg_order(args: &[Arg], diagnostics: &mut Vec<Diagnostic>) {
+    let named = args.iter().position(|a| matches!(a, Arg::Named(..)));
+    let misplaced = args
+        .iter()
+        .skip(named.unwrap_or(args.len()))
+        .filter(|a| !matches!(a, Arg::Named(..)));
+    for arg in misplaced {
+        diagnostics.push(Diagnostic::new(
+            "Positional arguments must come before named arguments.",
+            arg.span(),
+        ));
+    }
+}
+
+/// Matches the arguments of a call against the parameters of the called function. The positions
+/// of the arguments after a spread are not known, so missing or extra arguments are not reported
+/// for such calls.
+fn check_args(
+    Ident { name, .. }: &Ident,
+    args: &[Arg],
+    range: &Range<usize>,
+    func: &Func,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let params = &func.params;
+    let spread = args.iter().any(|a| matches!(a, Arg::Spread(..)));
+    let positional = args
+        .iter()
+        .filter(|a| matches!(a, Arg::Positional(_)))
+        .count();
+    if !spread && positional > params.len() {
+        diagnostics.push(Diagnostic::new(
+            format!(
+                "`{}` takes {} arguments but {} are given.",
+                name,
+                params.len(),
+                positional
+            ),
+            range.clone(),
+        ));
+    }
+    let mut given = (0..params.len())
+        .map(|i| !spread && i < positional)
+        .collect::<Vec<_>>();
+    for arg in args {
+        if let Arg::Named(
+            Ident {
+                name: arg_name,
+                range: arg_range,
+            },
+            _,
+        ) = arg
+        {
+            let param = params
+                .iter()
+                .position(|p| param_name(&p.pattern) == Some(arg_name));
+            match param {
+                Some(i) if given[i] => diagnostics.push(Diagnostic::new(
+                    format!(
+                        "Argument `{}` of `{}` is given more than once.",
+                        arg_name, name
+                    ),
+                    arg_range.clone(),
+                )),
+                Some(i) => given[i] = true,
+                None => diagnostics.push(Diagnostic::new(
+                    format!("Unknown argument `{}` of `{}`.", arg_name, name),
+                    arg_range.clone(),
+                )),
+            }
+        }
+    }
+    if !spread {
+        for (param, _) in params.iter().zip(given).filter(|(_, given)| !given) {
+            let param = param_name(&param.pattern)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("{:?}", param.pattern));
+            diagnostics.push(Diagnostic::new(
+                format!("Missing argument `{}` of `{}`.", param, name),
+                range.clone(),
+            ));
+        }
+    }
+}
+
+/// The name a parameter could be given by, which is only available for a parameter of a variable.
+fn param_name(param: &Pattern) -> Option<&str> {
+    match param {
+        Pattern::Var(VarDef {
+            name: Ident { name, .. },
+            ..
+        }) => Some(name),
+        _ => None,
+    }
+}
+
+/// The attributes known to the compiler.
+const ATTRIBUTES: &[&str] = &["deprecated", "doc", "inline", "test"];
+
+fn check_attributes(e: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+    for attribute in e.attributes() {
+        if !ATTRIBUTES.contains(&attribute.name()) {
+            diagnostics.push(Diagnostic::warning(
+                format!("Unknown attribute `#[{}]`.", attribute.name()),
+                attribute.range.clone(),
+            ));
+        }
+    }
+}
+
+/// Collects `e` and all the expressions inside it.
+fn walk<'a>(e: &'a Expr, exprs: &mut Vec<&'a Expr>) {
+    exprs.push(e);
+    for child in e.children() {
+        walk(child, exprs);
+    }
+}
+
+/// The declared enums, indexed by the names of their variants, which could be declared by more than
+/// one enum.
+struct Enums<'a>(HashMap<&'a str, Vec<(&'a EnumDecl, &'a Variant)>>);
+
+/// A pattern reduced to what matters for exhaustiveness.
+#[derive(Clone)]
+enum Pat<'a> {
+    /// Matches anything.
+    Wild,
+    Ctor(Ctor<'a>, Vec<Pat<'a>>),
+    /// Whose matched values are not known, such as type tests.
+    Opaque,
+}
+
+#[derive(Clone)]
+enum Ctor<'a> {
+    /// A variant of the enum, by its name.
+    Variant(&'a EnumDecl, &'a str),
+    Tuple(usize),
+    Lit(String),
+}
+
+impl PartialEq for Ctor<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Ctor::Variant(a, x), Ctor::Variant(b, y)) => std::ptr::eq(*a, *b) && x == y,
+            (Ctor::Tuple(a), Ctor::Tuple(b)) => a == b,
+            (Ctor::Lit(a), Ctor::Lit(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl<'a> Enums<'a> {
+    /// The variant `name` refers to in patterns using the variant names `used`. A name declared by
+    /// more than one enum refers to the one declaring the most of `used`, or to none of them if
+    /// there is a tie.
+    fn resolve(&self, name: &str, used: &HashSet<&str>) -> Option<(&'a EnumDecl, &'a Variant)> {
+        let candidates = self.0.get(name)?;
+        let score = |decl: &EnumDecl| {
+            decl.variants
+                .iter()
+                .filter(|v| used.contains(v.name.name.as_str()))
+                .count()
+        };
+        let best = candidates.iter().map(|(decl, _)| score(decl)).max()?;
+        let mut best = candidates.iter().filter(|(decl, _)| score(decl) == best);
+        match (best.next(), best.next()) {
+            (Some(&found), None) => Some(found),
+            _ => None,
+        }
+    }
+
+    fn lower(&self, pattern: &'a Pattern, used: &HashSet<&str>) -> Pat<'a> {
+        match pattern {
+            Pattern::Wildcard(_) => Pat::Wild,
+            Pattern::Var(VarDef { name, ty: None }) => match self.resolve(&name.name, used) {
+                Some((decl, v)) if v.payload.is_empty() => {
+                    Pat::Ctor(Ctor::Variant(decl, &v.name.name), Vec::new())
+                }
+                // Reported by `check_bare_variants`.
+                Some(_) => Pat::Opaque,
+                None if self.0.contains_key(name.name.as_str()) => Pat::Opaque,
+                None => Pat::Wild,
+            },
+            Pattern::Var(VarDef { ty: Some(_), .. }) => Pat::Opaque,
+            Pattern::Tuple(items, _) => Pat::Ctor(
+                Ctor::Tuple(items.len()),
+                items.iter().map(|p| self.lower(p, used)).collect(),
+            ),
+            Pattern::Variant(name, args, _) => match self.resolve(&name.name, used) {
+                Some((decl, v)) if v.payload.len() == args.len() => Pat::Ctor(
+                    Ctor::Variant(decl, &v.name.name),
+                    args.iter().map(|p| self.lower(p, used)).collect(),
+                ),
+                _ => Pat::Opaque,
+            },
+            Pattern::LitNum(n, _) => Pat::Ctor(Ctor::Lit(format!("{:?}", n)), Vec::new()),
+            Pattern::LitStr(s, _) => Pat::Ctor(Ctor::Lit(format!("{:?}", s)), Vec::new()),
+            Pattern::Record(_, fields, _) => {
+                let wild = fields.iter().all(|f| {
+                    f.pattern
+                        .iter()
+                        .all(|p| matches!(self.lower(p, used), Pat::Wild))
+                });
+                if wild {
+                    Pat::Wild
+                } else {
+                    Pat::Opaque
+                }
+            }
+            Pattern::Array(..) | Pattern::Rest(..) => Pat::Opaque,
+        }
+    }
+
+    /// Finds values matched by none of `rows`, each having `width` patterns. Returns an example of
+    /// such values, or `None` if there is none or it could not be known.
+    fn witness(&self, rows: &[Vec<Pat<'a>>], width: usize) -> Option<Vec<String>> {
+        if width == 0 {
+            return if rows.is_empty() {
+                Some(Vec::new())
+            } else {
+                None
+            };
+        }
+        if rows.iter().any(|r| matches!(r[0], Pat::Opaque)) {
+            return None;
+        }
+        let head = rows.iter().find_map(|r| match &r[0] {
+            Pat::Ctor(c, _) => Some(c),
+            _ => None,
+        });
+        let signature = match head {
+            Some(Ctor::Variant(decl, _)) => Some(
+                decl.variants
+                    .iter()
+                    .map(|v| (Ctor::Variant(decl, &v.name.name), v.payload.len()))
+                    .collect(),
+            ),
+            Some(Ctor::Tuple(n)) => Some(vec![(Ctor::Tuple(*n), *n)]),
+            Some(Ctor::Lit(_)) | None => None,
+        };
+        match signature {
+            Some(signature) => signature.into_iter().find_map(|(ctor, arity)| {
+                let rows = specialize(rows, &ctor, arity);
+                let mut witness = self.witness(&rows, arity + width - 1)?;
+                let args = witness.drain(..arity).collect::<Vec<_>>();
+                witness.insert(
+                    0,
+                    match ctor {
+                        Ctor::Variant(_, v) if args.is_empty() => v.to_string(),
+                        Ctor::Variant(_, v) => format!("{}({})", v, args.join(", ")),
+                        Ctor::Tuple(1) => format!("({},)", args[0]),
+                        Ctor::Tuple(_) => format!("({})", args.join(", ")),
+                        Ctor::Lit(l) => l,
+                    },
+                );
+                Some(witness)
+            }),
+            None => {
+                let rows = rows
+                    .iter()
+                    .filter(|r| matches!(r[0], Pat::Wild))
+                    .map(|r| r[1..].to_vec())
+                    .collect::<Vec<_>>();
+                let mut witness = self.witness(&rows, width - 1)?;
+                witness.insert(0, "_".to_string());
+                Some(witness)
+            }
+        }
+    }
+}
+
+/// Keeps the rows matching `ctor`, with its arguments expanded in place of the first pattern.
+fn specialize<'a>(rows: &[Vec<Pat<'a>>], ctor: &Ctor<'a>, arity: usize) -> Vec<Vec<Pat<'a>>> {
+    rows.iter()
+        .filter_map(|r| {
+            let args = match &r[0] {
+                Pat::Ctor(c, args) if c == ctor => args.clone(),
+                Pat::Wild => vec![Pat::Wild; arity],
+                _ => return None,
+            };
+            Some(args.into_iter().chain(r[1..].iter().cloned()).collect())
+        })
+        .collect()
+}
+
+/// Reports a `match` whose arms do not cover all the values, which could be known only if the
+/// arms match on declared enum variants, tuples or literals.
+fn check_exhaustive(m: &Match, enums: &Enums, diagnostics: &mut Vec<Diagnostic>) {
+    let mut used = HashSet::new();
+    for arm in &m.arms {
+        variant_names(&arm.pattern, &mut used);
+    }
+    for arm in &m.arms {
+        check_bare_variants(&arm.pattern, enums, &used, diagnostics);
+    }
+    let rows = m
+        .arms
+        .iter()
+        .filter(|arm| arm.guard.is_none())
+        .map(|arm| vec![enums.lower(&arm.pattern, &used)])
+        .collect::<Vec<_>>();
+    if let Some(witness) = enums.witness(&rows, 1) {
+        diagnostics.push(Diagnostic::new(
+            format!("Non-exhaustive match, `{}` is not covered.", witness[0]),
+            m.range.clone(),
+        ));
+    }
+}
+
+/// Reports the names in `p` of enum variants with payloads, which would otherwise bind anything.
+fn check_bare_variants(
+    p: &Pattern,
+    enums: &Enums,
+    used: &HashSet<&str>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    match p {
+        Pattern::Var(VarDef { name, ty: None }) => match enums.resolve(&name.name, used) {
+            Some((_, v)) if !v.payload.is_empty() => diagnostics.push(Diagnostic::new(
+                format!(
+                    "Variant `{}` has a payload, match it by `{}({})`.",
+                    name.name,
+                    name.name,
+                    vec!["_"; v.payload.len()].join(", ")
+                ),
+                name.range.clone(),
+            )),
+            _ => {}
+        },
+        Pattern::Variant(_, items, _) | Pattern::Tuple(items, _) | Pattern::Array(items, _) => {
+            for p in items {
+                check_bare_variants(p, enums, used, diagnostics);
+            }
+        }
+        Pattern::Record(_, fields, _) => {
+            for p in fields.iter().filter_map(|f| f.pattern.as_ref()) {
+                check_bare_variants(p, enums, used, diagnostics);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects the names in `p` which could be enum variants.
+fn variant_names<'a>(p: &'a Pattern, names: &mut HashSet<&'a str>) {
+    match p {
+        Pattern::Var(VarDef { name, ty: None }) => {
+            names.insert(&name.name);
+        }
+        Pattern::Variant(name, items, _) => {
+            names.insert(&name.name);
+            for p in items {
+                variant_names(p, names);
+            }
+        }
+        Pattern::Tuple(items, _) | Pattern::Array(items, _) => {
+            for p in items {
+                variant_names(p, names);
+            }
+        }
+        Pattern::Record(_, fields, _) => {
+            for p in fields.iter().filter_map(|f| f.pattern.as_ref()) {
+                variant_names(p, names);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,93 @@
+use std::ops::Range;
+
+use crate::lexer::Token;
+
+/// An error or warning found in the source, along with the range of characters it is about.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    pub message: String,
+    pub range: Range<usize>,
+    pub severity: Severity,
+    /// The path of the module the range is in, if compiled as one of several modules.
+    pub module: Option<String>,
+    /// Other ranges the diagnostic refers to, each with a note on why.
+    pub related: Vec<(String, Range<usize>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Severity {
+    Error,
+    /// Which does not stop the source from compiling.
+    Warning,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, range: Range<usize>) -> Self {
+        Diagnostic {
+            message: message.into(),
+            range,
+            severity: Severity::Error,
+            module: None,
+            related: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, range: Range<usize>) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(message, range)
+        }
+    }
+
+    pub(crate) fn with_related(mut self, note: impl Into<String>, range: Range<usize>) -> Self {
+        self.related.push((note.into(), range));
+        self
+    }
+
+    pub(crate) fn in_module(self, module: impl Into<String>) -> Self {
+        Diagnostic {
+            module: Some(module.into()),
+            ..self
+        }
+    }
+
+    /// Converts an error of a parser running on characters, whose position is a character offset.
+    pub(crate) fn from_chars_error(error: pom::Error) -> Self {
+        let position = error_position(&error).unwrap_or_default();
+        Diagnostic::new(error_message(error), position..position + 1)
+    }
+
+    /// Converts an error of a parser running on `tokens`, whose position is a token index.
+    pub(crate) fn from_tokens_error(error: pom::Error, tokens: &[Token]) -> Self {
+        let range = match error_position(&error) {
+            Some(position) => tokens
+                .get(position)
+                .or_else(|| tokens.last())
+                .map(|t| t.1.clone()),
+            None => tokens.last().map(|t| t.1.end..t.1.end),
+        };
+        Diagnostic::new(error_message(error), range.unwrap_or_default())
+    }
+}
+
+fn error_position(error: &pom::Error) -> Option<usize> {
+    match error {
+        pom::Error::Incomplete => None,
+        pom::Error::Mismatch { position, .. }
+        | pom::Error::Conversion { position, .. }
+        | pom::Error::Expect { position, .. }
+        | pom::Error::Custom { position, .. } => Some(*position),
+    }
+}
+
+fn error_message(error: pom::Error) -> String {
+    match error {
+        pom::Error::Incomplete => "Unexpected end of input.".to_string(),
+        pom::Error::Mismatch { message, .. }
+        | pom::Error::Conversion { message, .. }
+        | pom::Error::Expect { message, .. }
+        | pom::Error::Custom { message, .. } => message,
+    }
+}
@@ -0,0 +1,38 @@
+//! The versioned document tokens and ASTs are serialized in, whose shape is described in
+//! `docs/serialization.md`.
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+/// The version of the serialized shape of tokens and ASTs, which is increased on every change of
+/// the shape.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Tokens or an AST with the version of the shape they are serialized in, where a document of
+/// another version could not be deserialized.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document<T> {
+    #[serde(deserialize_with = "version")]
+    pub version: u32,
+    pub content: T,
+}
+
+impl<T> Document<T> {
+    pub fn new(content: T) -> Self {
+        Document {
+            version: FORMAT_VERSION,
+            content,
+        }
+    }
+}
+
+fn version<'de, D: Deserializer<'de>>(d: D) -> Result<u32, D::Error> {
+    let version = u32::deserialize(d)?;
+    if version == FORMAT_VERSION {
+        Ok(version)
+    } else {
+        Err(D::Error::custom(format!(
+            "unsupported format version {}, expected {}",
+            version, FORMAT_VERSION
+        )))
+    }
+}
@@ -1,15 +1,24 @@
 use pom::parser::{Parser, *};
-use std::{fmt::Debug, ops::Range, str::FromStr, usize};
+use std::{fmt::Debug, ops::Range, str::FromStr};
+
+use crate::diagnostic::Diagnostic;
 
 use TokenValue::*;
 
 type TokenParser<'a> = Parser<'a, char, TokenValue>;
 
+/// The kinds of tokens, where `Line` starts each line, and `BeginBlock` and `EndBlock` enclose the
+/// lines of an indented block.
 #[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
 pub enum TokenValue {
     Line(usize),
     Ident(String),
     Punct(&'static [char]),
+    Keyword(&'static str),
+    Label(String),
+    /// A line of doc comment written after `///`.
+    Doc(String),
     LitStr(String),
     LitNum(f64),
     BeginBlock,
@@ -17,12 +26,14 @@ pub enum TokenValue {
 }
 
 impl TokenValue {
-    pub fn token(self) -> Token {
+    pub(crate) fn token(self) -> Token {
         Token(self, 0..0)
     }
 }
 
+/// A token with the range of characters it is written in.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token(pub TokenValue, pub Range<usize>);
 
 impl std::fmt::Display for Token {
@@ -37,39 +48,144 @@ impl PartialEq for Token {
     }
 }
 
+/// The serialized form of `TokenValue`, where punctuations and keywords are strings, deserialized
+/// into the same ones in `PUNCTS` and `KEYWORDS`.
+#[cfg(feature = "serde")]
+mod serde_token {
+    use std::convert::TryFrom;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    pub enum TokenValue {
+        Line(usize),
+        Ident(String),
+        Punct(String),
+        Keyword(String),
+        Label(String),
+        Doc(String),
+        LitStr(String),
+        LitNum(f64),
+        BeginBlock,
+        EndBlock,
+    }
+
+    impl From<super::TokenValue> for TokenValue {
+        fn from(value: super::TokenValue) -> Self {
+            match value {
+                super::Line(ind) => TokenValue::Line(ind),
+                super::Ident(s) => TokenValue::Ident(s),
+                super::Punct(p) => TokenValue::Punct(p.iter().collect()),
+                super::Keyword(k) => TokenValue::Keyword(k.to_string()),
+                super::Label(s) => TokenValue::Label(s),
+                super::Doc(s) => TokenValue::Doc(s),
+                super::LitStr(s) => TokenValue::LitStr(s),
+                super::LitNum(n) => TokenValue::LitNum(n),
+                super::BeginBlock => TokenValue::BeginBlock,
+                super::EndBlock => TokenValue::EndBlock,
+            }
+        }
+    }
+
+    impl TryFrom<TokenValue> for super::TokenValue {
+        type Error = String;
+
+        fn try_from(value: TokenValue) -> Result<Self, String> {
+            Ok(match value {
+                TokenValue::Line(ind) => super::Line(ind),
+                TokenValue::Ident(s) => super::Ident(s),
+                TokenValue::Punct(p) => super::PUNCTS
+                    .iter()
+                    .find(|q| q.iter().copied().eq(p.chars()))
+                    .map(|&q| super::Punct(q))
+                    .ok_or_else(|| format!("unknown punctuation `{}`", p))?,
+                TokenValue::Keyword(k) => super::KEYWORDS
+                    .iter()
+                    .find(|&&q| q == k)
+                    .map(|&q| super::Keyword(q))
+                    .ok_or_else(|| format!("unknown keyword `{}`", k))?,
+                TokenValue::Label(s) => super::Label(s),
+                TokenValue::Doc(s) => super::Doc(s),
+                TokenValue::LitStr(s) => super::LitStr(s),
+                TokenValue::LitNum(n) => super::LitNum(n),
+                TokenValue::BeginBlock => super::BeginBlock,
+                TokenValue::EndBlock => super::EndBlock,
+            })
+        }
+    }
+
+    impl Serialize for super::TokenValue {
+        fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+            TokenValue::from(self.clone()).serialize(s)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for super::TokenValue {
+        fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+            super::TokenValue::try_from(TokenValue::deserialize(d)?).map_err(D::Error::custom)
+        }
+    }
+}
+
 fn digit<'a>() -> Parser<'a, char, char> {
     one_of("0123456789")
 }
 
 fn space<'a>() -> Parser<'a, char, ()> {
-    one_of(" \t").repeat(0..).discard()
+    (one_of(" \t").discard() | comment()).repeat(0..).discard()
+}
+
+/// Comments are written after `//` until the end of the line, except for doc comments.
+fn comment<'a>() -> Parser<'a, char, ()> {
+    (seq(&['/', '/']) - !sym('/') - none_of("\r\n").repeat(..))
+        .discard()
+        .name("comment")
+}
+
+fn doc<'a>() -> TokenParser<'a> {
+    let text = seq(&['/', '/', '/']) * sym(' ').opt() * none_of("\r\n").repeat(..);
+    text.map(|text| Doc(text.into_iter().collect()))
+}
+
+fn new_line<'a>() -> Parser<'a, char, char> {
+    sym('\r') | sym('\n') | (sym('\r') - sym('\n'))
 }
 
 fn line<'a>() -> TokenParser<'a> {
-    let new_line = sym('\r') | sym('\n') | sym('\r') - sym('\n');
     let indent = (sym(' ') | sym('\t'))
         .repeat(..)
         .map(|i| Line(i.iter().map(|c| if *c == ' ' { 1 } else { 8 }).sum()));
-    (new_line * indent)
+    // Lines with only spaces or comments are skipped.
+    (new_line() * indent - space())
         .repeat(1..)
         .map(|c| c.into_iter().last().unwrap())
 }
 
 fn ident<'a>() -> TokenParser<'a> {
-    let alphabet_ = || {
-        is_a(|c| match c {
-            'a'..='z' => true,
-            'A'..='Z' => true,
-            '_' => true,
-            _ => false,
-        })
-    };
+    let alphabet_ = || is_a(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '_'));
     (alphabet_() - (alphabet_() | digit()).repeat(..))
         .collect()
-        .map(|s| Ident(s.into_iter().collect()))
+        .map(|s| {
+            let s = s.iter().collect::<String>();
+            match KEYWORDS.iter().find(|&&k| k == s) {
+                Some(k) => Keyword(k),
+                None => Ident(s),
+            }
+        })
 }
 
-pub const fn puncts(v: &str) -> &'static [char] {
+const KEYWORDS: &[&str] = &[
+    "break", "by", "const", "continue", "enum", "export", "if", "import", "in", "let", "match",
+    "return", "struct", "throw", "type",
+];
+
+fn label<'a>() -> TokenParser<'a> {
+    let alphabet_ = || is_a(|c| matches!(c, 'a'..='z' | 'A'..='Z' | '_'));
+    (sym('\'') * (alphabet_() - (alphabet_() | digit()).repeat(..)).collect())
+        .map(|s| Label(s.iter().collect()))
+}
+
+pub(crate) const fn puncts(v: &str) -> &'static [char] {
     let b = v.as_bytes();
     let mut i = 0;
     while i < PUNCTS.len() {
@@ -87,25 +203,30 @@ pub const fn puncts(v: &str) -> &'static [char] {
         }
         i += 1;
     }
-    return PUNCTS[1 / zero()];
-}
-
-const fn zero() -> usize {
-    1 - 1
+    panic!("unknown punctuation")
 }
 
 const PUNCTS: &[&[char]] = &[
+    &['.', '.', '.'],
+    &['.', '.', '='],
+    &['.', '.'],
     &['=', '='],
     &['!', '='],
     &['<', '='],
     &['>', '='],
     &['=', '>'],
     &['!', '?'],
+    &['!', '!'],
+    &['^', '^'],
     &['&', '&'],
     &['|', '|'],
+    &['|', '>'],
+    &['?', '?'],
+    &['?', '.'],
     &[';'],
     &[':'],
     &['?'],
+    &['!'],
     &['^'],
     &[','],
     &['('],
@@ -121,6 +242,7 @@ const PUNCTS: &[&[char]] = &[
     &['='],
     &['.'],
     &['@'],
+    &['#'],
     &['['],
     &[']'],
     &['{'],
@@ -128,9 +250,14 @@ const PUNCTS: &[&[char]] = &[
 ];
 
 fn punct<'a>() -> TokenParser<'a> {
-    let mut result = seq(&PUNCTS[0]).map(|_| Punct(PUNCTS[0]));
-    for i in 1..PUNCTS.len() {
-        result = result | seq(PUNCTS[i]).map(move |_| Punct(PUNCTS[i]));
+    let mut result = seq(PUNCTS[0]).map(|_| Punct(PUNCTS[0]));
+    for &p in &PUNCTS[1..] {
+        let punct = match p {
+            // `?.{` is a `?` followed by a record rather than a member access.
+            ['?', '.'] => seq(p) - !(space() * sym('{')),
+            _ => seq(p),
+        };
+        result = result | punct.map(move |_| Punct(p));
     }
     result
 }
@@ -150,13 +277,13 @@ fn lit_string<'a>() -> TokenParser<'a> {
 }
 
 fn lit_number<'a>() -> TokenParser<'a> {
-    let integer = one_of("123456789") - digit().repeat(0..) | sym('0');
+    let integer = (one_of("123456789") - digit().repeat(0..)) | sym('0');
     let frac = sym('.') + digit().repeat(1..);
     let exp = one_of("eE") + one_of("+-").opt() + digit().repeat(1..);
     let number = sym('-').opt() + integer + frac.opt() + exp.opt();
     number
         .collect()
-        .convert(|s| f64::from_str(s.into_iter().collect::<String>().as_str()).map(LitNum))
+        .convert(|s| f64::from_str(s.iter().collect::<String>().as_str()).map(LitNum))
 }
 
 fn with_pos<'a>(origin: Parser<'a, char, TokenValue>) -> Parser<'a, char, Token> {
@@ -165,21 +292,36 @@ fn with_pos<'a>(origin: Parser<'a, char, TokenValue>) -> Parser<'a, char, Token>
     })
 }
 
-pub fn lexer<'a>() -> Parser<'a, char, Vec<Token>> {
-    (space().opt() * with_pos(line() | ident() | punct() | lit_string() | lit_number())).repeat(..)
+fn lexer<'a>() -> Parser<'a, char, Vec<Token>> {
+    (space().opt()
+        * with_pos(line() | ident() | label() | doc() | punct() | lit_string() | lit_number()))
+    .repeat(..)
         - space()
         - end()
 }
 
-pub fn arrange<'a>() -> Parser<'a, Token, Vec<Token>> {
+fn arrange<'a>() -> Parser<'a, Token, Vec<Token>> {
     Parser::new(|origin: &[Token], pos| {
-        let mut result = Vec::new();
-        result.reserve((origin.len() as f64 * 1.5) as _);
+        let mut result = Vec::with_capacity((origin.len() as f64 * 1.5) as _);
         let mut levels = Vec::new();
-        for Token(token, range) in origin {
+        for (i, Token(token, range)) in origin.iter().enumerate() {
             if let &Line(ind) = token {
                 let mut last = *levels.last().unwrap_or(&0);
                 let pos = range.start;
+                if matches!(origin.get(i + 1), Some(Token(Punct(p), _)) if p[..] == ['|', '>']) {
+                    // A line starting with `|>` continues the line at the same or an outer
+                    // indentation, ending the blocks indented deeper than it. It joins the line
+                    // before it if indented deeper, or is parsed as a line continuing it.
+                    while ind < last {
+                        levels.pop();
+                        last = *levels.last().unwrap_or(&0);
+                        result.push(Token(EndBlock, pos..pos));
+                    }
+                    if ind == last {
+                        result.push(Token(Line(0), range.clone()));
+                    }
+                    continue;
+                }
                 if ind > last {
                     result.push(Token(BeginBlock, pos..pos));
                     levels.push(ind);
@@ -203,11 +345,57 @@ pub fn arrange<'a>() -> Parser<'a, Token, Vec<Token>> {
             }
         }
 
-        let last_pos = result.last().unwrap().1.end;
-        for _ in levels.into_iter() {
+        let last_pos = result.last().map_or(0, |t| t.1.end);
+        for _ in levels.iter() {
             result.push(Token(EndBlock, last_pos..last_pos));
         }
 
         Ok((result, pos))
     })
 }
+
+/// Splits `src` into tokens arranged into blocks, positioned by the character offsets in `src`.
+pub fn tokenize(src: &str) -> Result<Vec<Token>, Diagnostic> {
+    // The leading line break makes the first line a `Line` like all the others.
+    let chars = Some('\n')
+        .into_iter()
+        .chain(src.chars())
+        .collect::<Vec<_>>();
+    let shift = |e: pom::Error| {
+        let Diagnostic { message, range, .. } = match e {
+            // Only the end of the input is left to fail on, at a character no token starts with.
+            pom::Error::Mismatch { position, .. } => {
+                // A string literal fails at an unknown escape sequence, or runs out of input.
+                let unterminated = ("Unterminated string literal.".to_string(), position);
+                let (message, at) = match (lit_string().method)(&chars, position) {
+                    Err(pom::Error::Mismatch { position: at, .. }) if chars[at] == '\\' => {
+                        match chars.get(at + 1) {
+                            Some(c) => (format!("Unknown escape sequence `\\{}`.", c), at),
+                            None => unterminated,
+                        }
+                    }
+                    Err(pom::Error::Incomplete) => unterminated,
+                    _ => (format!("Unexpected `{}`.", chars[position]), position),
+                };
+                Diagnostic::new(message, at..at + 1)
+            }
+            e => Diagnostic::from_chars_error(e),
+        };
+        Diagnostic::new(
+            message,
+            range.start.saturating_sub(1)..range.end.saturating_sub(1),
+        )
+    };
+    let mut tokens = lexer().parse(chars.as_slice()).map_err(shift)?;
+    for Token(_, range) in &mut tokens {
+        *range = range.start.saturating_sub(1)..range.end.saturating_sub(1);
+    }
+    // Blank lines at the end, or a source of only blank lines, end the input instead.
+    while let Some(Token(Line(_), _)) = tokens.last() {
+        tokens.pop();
+    }
+    let tokens = arrange()
+        .parse(tokens.as_ref())
+        .map_err(Diagnostic::from_chars_error)?;
+    Ok(tokens)
+}
@@ -1,21 +1,70 @@
-use ast::Block;
-use lexer::{arrange, lexer};
-use parser::parser;
+use ast::{Block, Expr, TypeExpr};
+use check::check;
+use parser::parse;
 
-mod ast;
+pub use diagnostic::{Diagnostic, Severity};
+#[cfg(feature = "serde")]
+pub use document::{Document, FORMAT_VERSION};
+pub use lexer::{tokenize, Token, TokenValue};
+pub use module::{compile_modules, FsLoader, MemoryLoader, Module, ModuleLoader};
+
+pub mod arena;
+pub mod ast;
+mod check;
+mod diagnostic;
+#[cfg(feature = "serde")]
+mod document;
 mod lexer;
+mod module;
 mod parser;
-mod utils;
-
-pub fn compile<'a>(src: &'a str) -> pom::Result<Block> {
-    let chars = Some('\n')
-        .into_iter()
-        .chain(src.chars())
-        .collect::<Vec<_>>();
-    let tokens = lexer().parse(chars.as_slice())?;
-    let tokens = arrange().parse(tokens.as_ref())?;
-    let ast = parser().parse(tokens.as_ref())?;
-    Ok(ast)
+pub mod visit;
+
+pub fn compile(src: &str) -> Result<Block, Vec<Diagnostic>> {
+    compile_with_warnings(src).map(|(ast, _)| ast)
+}
+
+/// Compiles `src` like `compile`, also giving the warnings found in it.
+pub fn compile_with_warnings(src: &str) -> Result<(Block, Vec<Diagnostic>), Vec<Diagnostic>> {
+    let (ast, diagnostics) = parse_partial(src);
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    let diagnostics = check(&ast);
+    if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        Err(diagnostics)
+    } else {
+        Ok((ast, diagnostics))
+    }
+}
+
+/// Parses `src` as far as possible, giving the program with `Expr::Error` in place of the lines
+/// that could not be parsed, along with all the syntax errors found.
+pub fn parse_partial(src: &str) -> (Block, Vec<Diagnostic>) {
+    match tokenize(src) {
+        Ok(tokens) => parse(&tokens),
+        Err(e) => (
+            Block {
+                lines: Vec::new(),
+                range: 0..0,
+            },
+            vec![e],
+        ),
+    }
+}
+
+/// Parses `src` as a single expression.
+pub fn parse_expr(src: &str) -> Result<Expr, Vec<Diagnostic>> {
+    parser::parse_expr(&tokenize(src).map_err(|e| vec![e])?)
+}
+
+/// Parses `src` as a type expression.
+pub fn parse_type(src: &str) -> Result<TypeExpr, Vec<Diagnostic>> {
+    parser::parse_type(&tokenize(src).map_err(|e| vec![e])?)
+}
+
+/// Parses `src` as the lines of a block, without the checks of `compile`.
+pub fn parse_block(src: &str) -> Result<Block, Vec<Diagnostic>> {
+    parser::parse_block(&tokenize(src).map_err(|e| vec![e])?)
 }
 
 #[cfg(test)]
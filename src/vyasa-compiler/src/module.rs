@@ -0,0 +1,256 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    ast::{Block, Expr, FieldPattern, Ident, Operation, Pattern},
+    compile_with_warnings,
+    diagnostic::Diagnostic,
+    visit::{walk_expr, Visitor},
+};
+
+/// Maps the module paths written in `import` to their sources.
+pub trait ModuleLoader {
+    fn load(&self, path: &str) -> Result<String, String>;
+}
+
+/// Loads modules from the files at their paths relative to `root`, which could not be absolute or
+/// lead out of `root` by `..`.
+pub struct FsLoader {
+    root: PathBuf,
+}
+
+impl FsLoader {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsLoader { root: root.into() }
+    }
+}
+
+impl ModuleLoader for FsLoader {
+    fn load(&self, path: &str) -> Result<String, String> {
+        fs::read_to_string(self.root.join(normalize(path)?)).map_err(|e| e.to_string())
+    }
+}
+
+/// The path of a module without `.` components, so that the same module is not loaded twice by
+/// differently written paths.
+fn normalize(path: &str) -> Result<String, String> {
+    let mut parts = Vec::new();
+    for component in Path::new(path).components() {
+        match component {
+            Component::Normal(part) => parts.push(part.to_string_lossy()),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                return Err("the path leads out of the root of modules".to_string())
+            }
+        }
+    }
+    if parts.is_empty() {
+        return Err("the path is empty".to_string());
+    }
+    Ok(parts.join("/"))
+}
+
+/// Loads modules from sources kept in memory.
+#[derive(Default)]
+pub struct MemoryLoader(HashMap<String, String>);
+
+impl MemoryLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, path: impl Into<String>, src: impl Into<String>) {
+        self.0.insert(path.into(), src.into());
+    }
+}
+
+impl ModuleLoader for MemoryLoader {
+    fn load(&self, path: &str) -> Result<String, String> {
+        self.0
+            .get(path)
+            .cloned()
+            .ok_or_else(|| "no such module".to_string())
+    }
+}
+
+#[derive(Debug)]
+pub struct Module {
+    pub path: String,
+    pub ast: Block,
+    pub warnings: Vec<Diagnostic>,
+}
+
+impl Module {
+    /// The names exported by the module.
+    pub fn exports(&self) -> Vec<&Ident> {
+        let mut names = Vec::new();
+        for e in &self.ast.lines {
+            if let Expr::Export(e, _) = e.unattributed() {
+                match e.as_ref() {
+                    Expr::Let(decl, _) => names.extend(decl.pattern.bindings()),
+                    Expr::Struct(decl) => names.push(&decl.name),
+                    Expr::Enum(decl) => names.push(&decl.name),
+                    Expr::TypeAlias(alias) => names.push(&alias.name),
+                    _ => {}
+                }
+            }
+        }
+        names
+    }
+}
+
+/// Compiles the module at `entry` along with all the modules it imports, ordered so that each
+/// module comes after the modules it imports.
+pub fn compile_modules(
+    entry: &str,
+    loader: &dyn ModuleLoader,
+) -> Result<Vec<Module>, Vec<Diagnostic>> {
+    let mut modules = Modules {
+        loader,
+        modules: Vec::new(),
+        visited: HashSet::new(),
+        stack: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    match normalize(entry).and_then(|path| Ok((loader.load(&path)?, path))) {
+        Ok((src, path)) => modules.compile(&path, &src),
+        Err(e) => modules.diagnostics.push(
+            Diagnostic::new(format!("Could not load module `{}`: {}.", entry, e), 0..0)
+                .in_module(entry),
+        ),
+    }
+    if modules.diagnostics.is_empty() {
+        Ok(modules.modules)
+    } else {
+        Err(modules.diagnostics)
+    }
+}
+
+struct Modules<'a> {
+    loader: &'a dyn ModuleLoader,
+    modules: Vec<Module>,
+    visited: HashSet<String>,
+    /// The modules being compiled, each imported by the one before it.
+    stack: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Modules<'_> {
+    fn compile(&mut self, path: &str, src: &str) {
+        self.visited.insert(path.to_string());
+        let (ast, warnings) = match compile_with_warnings(src) {
+            Ok(compiled) => compiled,
+            Err(diagnostics) => {
+                let diagnostics = diagnostics.into_iter().map(|d| d.in_module(path));
+                self.diagnostics.extend(diagnostics);
+                return;
+            }
+        };
+        self.stack.push(path.to_string());
+        let mut imports = Imports(Vec::new());
+        imports.visit_block(&ast);
+        for (written, range, names) in imports.0 {
+            let import = match normalize(written) {
+                Ok(import) => import,
+                Err(e) => {
+                    self.diagnostics.push(
+                        Diagnostic::new(
+                            format!("Could not load module `{}`: {}.", written, e),
+                            range,
+                        )
+                        .in_module(path),
+                    );
+                    continue;
+                }
+            };
+            let import = import.as_str();
+            if let Some(i) = self.stack.iter().position(|p| p == import) {
+                let chain = self.stack[i..]
+                    .iter()
+                    .map(String::as_str)
+                    .chain(Some(import))
+                    .map(|p| format!("`{}`", p))
+                    .collect::<Vec<_>>();
+                self.diagnostics.push(
+                    Diagnostic::new(format!("Import cycle {}.", chain.join(" -> ")), range)
+                        .in_module(path),
+                );
+            } else if !self.visited.contains(import) {
+                match self.loader.load(import) {
+                    Ok(src) => self.compile(import, &src),
+                    Err(e) => {
+                        self.visited.insert(import.to_string());
+                        self.diagnostics.push(
+                            Diagnostic::new(
+                                format!("Could not load module `{}`: {}.", import, e),
+                                range,
+                            )
+                            .in_module(path),
+                        );
+                    }
+                }
+            }
+            self.check_imported(path, import, &names);
+        }
+        self.stack.pop();
+        self.modules.push(Module {
+            path: path.to_string(),
+            ast,
+            warnings: warnings.into_iter().map(|d| d.in_module(path)).collect(),
+        });
+    }
+
+    /// Reports the `names` taken out of the module `import` which it does not export. Modules that
+    /// are not compiled, as they fail or are still being compiled in a cycle, are not checked.
+    fn check_imported(&mut self, path: &str, import: &str, names: &[&Ident]) {
+        let module = match self.modules.iter().find(|m| m.path == import) {
+            Some(module) => module,
+            None => return,
+        };
+        let exports = module.exports();
+        for name in names {
+            if !exports.iter().any(|e| e.name == name.name) {
+                self.diagnostics.push(
+                    Diagnostic::new(
+                        format!("Module `{}` does not export `{}`.", import, name.name),
+                        name.range.clone(),
+                    )
+                    .in_module(path),
+                );
+            }
+        }
+    }
+}
+
+/// Collects the module paths imported, with the ranges of the `import` expressions, and the names
+/// taken out of the imported modules by record patterns.
+struct Imports<'a>(Vec<(&'a str, Range<usize>, Vec<&'a Ident>)>);
+
+impl<'a> Visitor<'a> for Imports<'a> {
+    fn visit_expr(&mut self, e: &'a Expr) {
+        let (pattern, value) = match e {
+            Expr::Let(decl, _) => (Some(&decl.pattern), &decl.value),
+            Expr::Operation(o) => match o.as_ref() {
+                Operation::Assign(pattern, value) => (Some(pattern), value),
+                _ => (None, e),
+            },
+            _ => (None, e),
+        };
+        if let Expr::Import(path, range) = value {
+            let names = match pattern {
+                Some(Pattern::Record(None, fields, _)) => fields
+                    .iter()
+                    .map(|FieldPattern { name, .. }| name)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            self.0.push((path, range.clone(), names));
+        } else {
+            walk_expr(self, e);
+        }
+    }
+}
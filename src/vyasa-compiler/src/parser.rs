@@ -1,46 +1,113 @@
-use lazy_static::lazy_static;
-
 use crate::lexer::TokenValue;
 use crate::{
-    ast::{Block, Expr, Ident, Operation, VarDef},
+    ast::{
+        Arg, Arm, Attribute, Block, EnumDecl, Expr, Field, FieldDef, FieldPattern, Func, Ident,
+        Let, Match, Member, Operation, Param, Pattern, Spanned, StructDecl, TypeAlias, TypeExpr,
+        VarDef, Variant,
+    },
+    diagnostic::Diagnostic,
     lexer::{puncts, Token},
-    utils::*,
 };
 use pom::parser::*;
+use std::{cell::Cell, ops::Range};
 
 type Parser<'a, O> = pom::parser::Parser<'a, Token, O>;
 
-#[derive(Debug, Clone, Copy)]
-struct Context {
-    indent: usize,
-}
-
-pub const BIN_OPS: &[(&str, usize)] = &[
-    ("?", 14),
-    ("!?", 14),
-    ("^", 14),
-    ("=", 14),
-    ("+", 4),
-    ("-", 4),
-    ("*", 3),
-    ("/", 3),
-    ("&", 8),
-    ("|", 10),
-    ("==", 7),
-    ("!=", 7),
-    (">", 6),
-    ("<", 6),
-    (">=", 6),
-    ("<=", 6),
-    ("&&", 11),
-    ("||", 12),
+#[derive(Clone, Copy, PartialEq)]
+enum Assoc {
+    Left,
+    Right,
+    /// Could not be chained, which is reported when reduced.
+    None,
+}
+
+/// The binary operators with their precedences, where a lower precedence binds tighter. The else
+/// part `!?` of `?` is not listed, since it is parsed along with the `?`.
+const BIN_OPS: &[(&str, usize, Assoc)] = &[
+    ("?", 15, Assoc::Right),
+    ("^", 15, Assoc::Right),
+    ("^^", 15, Assoc::Right),
+    ("=", 15, Assoc::Right),
+    ("!!", 15, Assoc::Right),
+    ("+", 4, Assoc::Left),
+    ("-", 4, Assoc::Left),
+    ("*", 3, Assoc::Left),
+    ("/", 3, Assoc::Left),
+    ("&", 8, Assoc::Left),
+    ("|", 10, Assoc::Left),
+    ("==", 7, Assoc::None),
+    ("!=", 7, Assoc::None),
+    (">", 6, Assoc::None),
+    ("<", 6, Assoc::None),
+    (">=", 6, Assoc::None),
+    ("<=", 6, Assoc::None),
+    ("&&", 11, Assoc::Left),
+    ("||", 12, Assoc::Left),
+    ("|>", 13, Assoc::Left),
+    ("??", 14, Assoc::Right),
+    ("..", 5, Assoc::Left),
+    ("..=", 5, Assoc::Left),
+    ("by", 5, Assoc::Left),
 ];
 
-fn reduce_binary(l: Expr, op: &str, r: Expr) -> Expr {
-    Expr::Operation(Box::new(match op {
+/// Reduces a binary operation, giving back the left operand along with the error if the operands
+/// are not valid for the operator.
+fn reduce_binary(l: Expr, op: &str, r: Expr) -> Result<Expr, Box<(Expr, String)>> {
+    Ok(Expr::Operation(Box::new(match op {
         "?" => Operation::If(l, r),
-        "^" => Operation::While(l, r),
-        "=" => Operation::Assign(l, r),
+        "^" => Operation::While(l, r, None),
+        "^^" => Operation::Generator(l, r, None),
+        "=" => match pattern(l) {
+            Ok(l) => Operation::Assign(l, r),
+            Err(l) => {
+                let message = format!("Invalid assignment target `{:?}`.", l);
+                return Err(Box::new((l, message)));
+            }
+        },
+        // `x !! e ? h` is parsed as `x !! (e ? h)`, whose condition is taken as the pattern.
+        "!!" => {
+            let handler = match r {
+                Expr::Operation(o) => match *o {
+                    Operation::If(p, handler) => Some((match_pattern(p), handler)),
+                    _ => None,
+                },
+                _ => None,
+            };
+            match handler {
+                Some((Ok(p), handler)) => Operation::Catch(l, p, handler),
+                Some((Err(p), _)) => {
+                    let message = format!("Invalid pattern `{:?}` after `!!`.", p);
+                    return Err(Box::new((l, message)));
+                }
+                None => {
+                    let message =
+                        "`!!` must be followed by a pattern and a handler, such as `x !! e ? 0`.";
+                    return Err(Box::new((l, message.to_string())));
+                }
+            }
+        }
+        ".." | "..=" if is_range(&l) => {
+            return Err(Box::new((l, "Ranges could not be chained.".to_string())));
+        }
+        ".." => Operation::Range(l, r, None),
+        "..=" => Operation::RangeInclusive(l, r, None),
+        "by" => match l {
+            Expr::Operation(mut o) => match o.as_mut() {
+                Operation::Range(_, _, step @ None)
+                | Operation::RangeInclusive(_, _, step @ None) => {
+                    *step = Some(r);
+                    return Ok(Expr::Operation(o));
+                }
+                _ => {
+                    let message = "`by` must follow a range without a step.".to_string();
+                    return Err(Box::new((Expr::Operation(o), message)));
+                }
+            },
+            l => {
+                let message = "`by` must follow a range without a step.".to_string();
+                return Err(Box::new((l, message)));
+            }
+        },
         "+" => Operation::Add(l, r),
         "-" => Operation::Sub(l, r),
         "*" => Operation::Mul(l, r),
@@ -55,39 +122,245 @@ fn reduce_binary(l: Expr, op: &str, r: Expr) -> Expr {
         "<=" => Operation::LE(l, r),
         "&&" => Operation::And(l, r),
         "||" => Operation::Or(l, r),
+        "??" => Operation::Default(l, r),
+        // `x |> f(y)` is `f(x, y)`, where `f` alone is the same as `f()`.
+        "|>" => {
+            let (name, mut args, range) = match r {
+                Expr::Call(name, args, range) => (name, args, range),
+                Expr::Var(VarDef { name, ty: None }) => {
+                    let range = name.range.clone();
+                    (name, Vec::new(), range)
+                }
+                _ => {
+                    let message = "`|>` must be followed by a function call.".to_string();
+                    return Err(Box::new((l, message)));
+                }
+            };
+            let range = l.span().start..range.end;
+            args.insert(0, Arg::Positional(l));
+            return Ok(Expr::Call(name, args, range));
+        }
         _ => panic!(),
-    }))
+    })))
 }
 
-lazy_static! {
-    pub static ref BIN_OP_TOKENS: Vec<Token> = {
-        BIN_OPS
-            .iter()
-            .map(|(s, _)| TokenValue::Punct(puncts(s)).token())
-            .collect()
+/// The operands and the operator of `e` if it is a comparison not in parentheses.
+fn comparison(e: &Expr) -> Option<(&Expr, &'static str, &Expr)> {
+    match e {
+        Expr::Operation(o) if o.is_comparison() => match o.as_ref() {
+            Operation::EQ(l, r)
+            | Operation::NE(l, r)
+            | Operation::GT(l, r)
+            | Operation::LT(l, r)
+            | Operation::GE(l, r)
+            | Operation::LE(l, r) => Some((l, o.operator(), r)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Reports `l op r` if it chains comparisons, at the position of the later comparison operator,
+/// where `position` is that of `op`.
+fn check_chain(
+    input: &[Token],
+    l: &Expr,
+    op: &str,
+    r: &Expr,
+    position: usize,
+) -> Option<(String, usize)> {
+    if !matches!(op, "==" | "!=" | ">" | "<" | ">=" | "<=") {
+        return None;
+    }
+    let text = |e: &Expr| source_text(input, e.span());
+    let relational = |op: &str| matches!(op, ">" | "<" | ">=" | "<=");
+    let (suggestion, position) = match (comparison(l), comparison(r)) {
+        (Some((a, inner, b)), _) if relational(inner) && relational(op) => {
+            let (a, b, c) = (text(a), text(b), text(r));
+            let suggestion = format!("{} {} {} && {} {} {}", a, inner, b, b, op, c);
+            (suggestion, position)
+        }
+        (Some((a, inner, b)), _) => {
+            let suggestion = format!("({} {} {}) {} {}", text(a), inner, text(b), op, text(r));
+            (suggestion, position)
+        }
+        (_, Some((b, inner, c))) => {
+            let suggestion = format!("{} {} ({} {} {})", text(l), op, text(b), inner, text(c));
+            // The inner operator is the first token after its left operand.
+            let end = b.span().end;
+            let inner_position = (position..input.len())
+                .find(|&i| input[i].1.start >= end)
+                .unwrap_or(position);
+            (suggestion, inner_position)
+        }
+        _ => return None,
     };
-    pub static ref BIN_OP_GROUPS: Vec<(usize, Vec<usize>)> = {
-        if let Some(min) = BIN_OPS.iter().map(|x| x.1).min() {
-            if let Some(max) = BIN_OPS.iter().map(|x| x.1).max() {
-                return (min..=max)
+    let message = if suggestion.contains("&&") {
+        format!(
+            "Comparison operators could not be chained, join the comparisons by `&&` such as `{}`.",
+            suggestion
+        )
+    } else {
+        format!(
+            "Comparison operators could not be chained, add parentheses such as `{}`.",
+            suggestion
+        )
+    };
+    Some((message, position))
+}
+
+/// The source of the tokens in `range`, with a space between the tokens written apart.
+fn source_text(input: &[Token], range: Range<usize>) -> String {
+    let mut text = String::new();
+    let mut last_end = None;
+    for Token(t, r) in input {
+        if r.start < range.start || r.end > range.end || r.start == r.end {
+            continue;
+        }
+        if matches!(last_end, Some(end) if end < r.start) {
+            text.push(' ');
+        }
+        match t {
+            TokenValue::Ident(s) => text.push_str(s),
+            TokenValue::Punct(p) => text.extend(p.iter()),
+            TokenValue::Keyword(k) => text.push_str(k),
+            TokenValue::Label(s) => {
+                text.push('\'');
+                text.push_str(s);
+            }
+            TokenValue::LitStr(s) => text.push_str(&format!("{:?}", s)),
+            TokenValue::LitNum(n) => text.push_str(&n.to_string()),
+            _ => {}
+        }
+        last_end = Some(r.end);
+    }
+    text
+}
+
+fn is_range(e: &Expr) -> bool {
+    match e {
+        Expr::Operation(o) => matches!(
+            o.as_ref(),
+            Operation::Range(..) | Operation::RangeInclusive(..)
+        ),
+        _ => false,
+    }
+}
+
+/// Converts an expression on the left of `=` into the pattern it is written as, or gives it back if
+/// it is not a pattern.
+fn pattern(e: Expr) -> Result<Pattern, Expr> {
+    to_pattern(e, false)
+}
+
+/// Converts an expression into a pattern like `pattern`, additionally accepting the refutable
+/// literal and variant patterns of `match` arms.
+fn match_pattern(e: Expr) -> Result<Pattern, Expr> {
+    to_pattern(e, true)
+}
+
+fn to_pattern(e: Expr, refutable: bool) -> Result<Pattern, Expr> {
+    fn elements(
+        elements: Vec<Expr>,
+        refutable: bool,
+        rest: bool,
+    ) -> Result<Vec<Pattern>, Vec<Expr>> {
+        let element = |e: Expr| match e {
+            Expr::Spread(e, range) if rest => match *e {
+                Expr::Var(VarDef {
+                    name: Ident { name, .. },
+                    ty: None,
+                }) if name == "_" => Ok(Pattern::Rest(None, range)),
+                Expr::Var(VarDef { name, ty: None }) => Ok(Pattern::Rest(Some(name), range)),
+                e => Err(Expr::Spread(Box::new(e), range)),
+            },
+            e => to_pattern(e, refutable),
+        };
+        if elements.iter().all(|e| element(e.clone()).is_ok()) {
+            Ok(elements
+                .into_iter()
+                .filter_map(|e| element(e).ok())
+                .collect())
+        } else {
+            Err(elements)
+        }
+    }
+    match e {
+        Expr::Var(VarDef {
+            name: Ident { name, range },
+            ty: None,
+        }) if name == "_" => Ok(Pattern::Wildcard(range)),
+        Expr::Var(v) => Ok(Pattern::Var(v)),
+        Expr::LitNum(n, range) if refutable => Ok(Pattern::LitNum(n, range)),
+        Expr::LitStr(s, range) if refutable => Ok(Pattern::LitStr(s, range)),
+        // Variant patterns are written like calls with only positional arguments.
+        Expr::Call(name, args, range) if refutable => {
+            let positional = args
+                .iter()
+                .map(|a| match a {
+                    Arg::Positional(e) => Some(e.clone()),
+                    _ => None,
+                })
+                .collect::<Option<Vec<_>>>();
+            match positional.map(|args| elements(args, refutable, false)) {
+                Some(Ok(args)) => Ok(Pattern::Variant(name, args, range)),
+                _ => Err(Expr::Call(name, args, range)),
+            }
+        }
+        Expr::Paren(e, range) => {
+            to_pattern(*e, refutable).map_err(|e| Expr::Paren(Box::new(e), range))
+        }
+        Expr::Tuple(items, range) => match elements(items, refutable, false) {
+            Ok(items) => Ok(Pattern::Tuple(items, range)),
+            Err(items) => Err(Expr::Tuple(items, range)),
+        },
+        Expr::Array(items, range) => {
+            let rests = items.iter().filter(|e| matches!(e, Expr::Spread(..)));
+            if rests.count() > 1 {
+                return Err(Expr::Array(items, range));
+            }
+            match elements(items, refutable, true) {
+                Ok(items) => Ok(Pattern::Array(items, range)),
+                Err(items) => Err(Expr::Array(items, range)),
+            }
+        }
+        Expr::Record(name, fields, range) => {
+            let valid = fields.iter().all(|f| {
+                f.value
+                    .iter()
+                    .all(|v| to_pattern(v.clone(), refutable).is_ok())
+            });
+            if valid {
+                let fields = fields
                     .into_iter()
-                    .filter_map(|i| {
-                        let group = BIN_OPS
-                            .iter()
-                            .enumerate()
-                            .filter_map(|(idx, &(_, p))| if p == i { Some(idx) } else { None })
-                            .collect::<Vec<_>>();
-                        if group.len() > 0 {
-                            Some((i, group))
-                        } else {
-                            None
-                        }
-                    })
-                    .collect();
+                    .map(|Field { name, value }| FieldPattern {
+                        name,
+                        pattern: value.and_then(|v| to_pattern(v, refutable).ok()),
+                    });
+                Ok(Pattern::Record(name, fields.collect(), range))
+            } else {
+                Err(Expr::Record(name, fields, range))
             }
         }
-        Vec::new()
-    };
+        e => Err(e),
+    }
+}
+
+/// A binary operator with its precedence and associativity, as listed in `BIN_OPS`.
+type BinOp = (&'static str, usize, Assoc);
+
+fn bin_op<'a>() -> Parser<'a, BinOp> {
+    any().convert(|Token(t, _)| {
+        BIN_OPS
+            .iter()
+            .find(|(s, _, _)| match &t {
+                TokenValue::Punct(p) => p.iter().copied().eq(s.chars()),
+                TokenValue::Keyword(k) => k == s,
+                _ => false,
+            })
+            .copied()
+            .ok_or("")
+    })
 }
 
 fn peek_before<'a, I, O: 'a, U: 'a>(
@@ -95,10 +368,8 @@ fn peek_before<'a, I, O: 'a, U: 'a>(
     t: pom::parser::Parser<'a, I, O>,
 ) -> pom::parser::Parser<'a, I, O> {
     pom::parser::Parser::new(move |target, start| {
-        if start > 0 {
-            if let Ok(_) = (b.method)(target, start - 1) {
-                return (t.method)(target, start);
-            }
+        if start > 0 && (b.method)(target, start - 1).is_ok() {
+            return (t.method)(target, start);
         }
 
         Err(pom::Error::Mismatch {
@@ -108,120 +379,737 @@ fn peek_before<'a, I, O: 'a, U: 'a>(
     })
 }
 
+/// Parses binary operations by precedence climbing over the operands of `non_left_recursive`.
 fn binary<'a>() -> Parser<'a, Expr> {
-    let cluster = non_left_recursive()
-        + (one_of(&**BIN_OP_TOKENS) + non_left_recursive()
-            | peek_before(
-                sym(TokenValue::EndBlock.token()),
-                sym(TokenValue::Line(0).token()),
-            ) * punct("!?")
-                + non_left_recursive())
-        .repeat(..);
-    cluster.map(|(mut first, mut rest)| {
-        let mut elses = Vec::new();
-        for (pri, group) in &*BIN_OP_GROUPS {
-            // Right to left when parsing = ? ^
-            let (mut i, step, on_reduce) = if *pri == 14 {
-                (rest.len() as isize - 1, -1, -1)
-            } else {
-                (0isize, 1, 0)
+    let (operand, op, else_) = operators();
+    let climbing = pom::parser::Parser::new(move |input: &'a [Token], start| {
+        let parsers = (&operand, &op, &else_);
+        let mut error = None;
+        let (e, end) = climb(parsers, input, start, usize::MAX, &mut error)?;
+        Ok((error.map_or(Ok(e), Err), end))
+    });
+    fatal_at(climbing)
+}
+
+/// The parsers of operands, binary operators and the `!?` of `?` that `climb` takes.
+fn operators<'a>() -> (Parser<'a, Expr>, Parser<'a, BinOp>, Parser<'a, Token>) {
+    // `!?` could also start the line after the block of a `?`.
+    let line_after_block = peek_before(
+        sym(TokenValue::EndBlock.token()),
+        sym(TokenValue::Line(0).token()),
+    );
+    (
+        member_access(),
+        bin_op(),
+        line_after_block.opt() * punct("!?"),
+    )
+}
+
+/// Continues a line parsed into `lhs` with the lines after it starting with `|>`, which pipe the
+/// value of a declaration or an assignment, or the whole line otherwise.
+fn pipe_lines<'a>() -> impl Fn(Expr, &'a [Token], usize) -> pom::Result<(Expr, usize)> {
+    let (operand, op, else_) = operators();
+    move |lhs, input, mut pos| {
+        let mut lhs = lhs;
+        while let (Some(Token(TokenValue::Line(_), _)), true) =
+            (input.get(pos), is_punct(input.get(pos + 1), "|>"))
+        {
+            let mut error = None;
+            let mut pipe = |value: Expr| {
+                let parsers = (&operand, &op, &else_);
+                climb_from(parsers, input, value, pos + 1, usize::MAX, &mut error)
             };
-            while i < (rest.len() as _) && i >= 0 {
-                if *pri == 14 && rest[i as usize].0 .0 == TokenValue::Punct(puncts("!?")) {
-                    elses.push(rest.remove(i as _).1);
-                    i += on_reduce;
-                } else if let Some(op) = group.iter().find_map(|&j| {
-                    if BIN_OP_TOKENS[j] == rest[i as usize].0 {
-                        Some(BIN_OPS[j].0)
-                    } else {
-                        None
-                    }
-                }) {
-                    let last = rest.remove(i as _).1;
-                    let first = if i == 0 {
-                        &mut first
-                    } else {
-                        &mut rest[i as usize - 1].1
-                    };
-                    if op == "?" && elses.len() > 0 {
-                        call_replace(first, |first| {
-                            Expr::Operation(Box::new(Operation::IfElse(
-                                first,
-                                last,
-                                elses.pop().unwrap(),
-                            )))
-                        });
-                    } else {
-                        call_replace(first, |first| reduce_binary(first, op, last));
+            let (e, end) = pipe_value(lhs, &mut pipe)?;
+            if let Some((message, position)) = error {
+                return Err(unrecoverable(message, position.unwrap_or(pos + 1)));
+            }
+            lhs = e;
+            pos = end;
+        }
+        Ok((lhs, pos))
+    }
+}
+
+/// Applies `pipe` to the value of a declaration or an assignment, or to `e` otherwise.
+fn pipe_value(
+    e: Expr,
+    pipe: &mut dyn FnMut(Expr) -> pom::Result<(Expr, usize)>,
+) -> pom::Result<(Expr, usize)> {
+    Ok(match e {
+        Expr::Let(decl, range) => {
+            let mut decl = *decl;
+            let (value, end) = pipe(decl.value)?;
+            let range = range.start..value.span().end;
+            decl.value = value;
+            (Expr::Let(Box::new(decl), range), end)
+        }
+        Expr::Operation(operation) => match *operation {
+            Operation::Assign(pattern, value) => {
+                let (value, end) = pipe(value)?;
+                (
+                    Expr::Operation(Box::new(Operation::Assign(pattern, value))),
+                    end,
+                )
+            }
+            operation => pipe(Expr::Operation(Box::new(operation)))?,
+        },
+        Expr::Attributed(attributes, e) => {
+            let (e, end) = pipe_value(*e, pipe)?;
+            (Expr::Attributed(attributes, Box::new(e)), end)
+        }
+        e => pipe(e)?,
+    })
+}
+
+/// An operand followed by accesses to its members, which bind tighter than all the operators.
+fn member_access<'a>() -> Parser<'a, Expr> {
+    let args = separated("(", arg, ")")
+        .convert(|(_, args, close)| comma_separated(args).map(|(args, _)| (args, close)));
+    let dot = punct(".").map(|_| false) | punct("?.").map(|_| true);
+    let member = dot + is_ident().convert(ident) + args.opt();
+    (non_left_recursive() + member.repeat(..)).map(|(e, members)| {
+        members.into_iter().fold(e, |e, ((safe, name), args)| {
+            let end = args
+                .as_ref()
+                .map_or(name.range.end, |(_, close)| close.1.end);
+            let range = e.span().start..end;
+            let args = args.map(|(args, _)| args);
+            let member = Member {
+                receiver: e,
+                name,
+                args,
+                safe,
+            };
+            Expr::Member(Box::new(member), range)
+        })
+    })
+}
+
+type Operators<'p, 'a> = (
+    &'p Parser<'a, Expr>,
+    &'p Parser<'a, BinOp>,
+    &'p Parser<'a, Token>,
+);
+
+/// Parses an operand followed by the operators binding no looser than `max`, keeping the first
+/// error of invalid operands in `error`.
+fn climb<'a>(
+    parsers: Operators<'_, 'a>,
+    input: &'a [Token],
+    start: usize,
+    max: usize,
+    error: &mut Option<(String, Option<usize>)>,
+) -> pom::Result<(Expr, usize)> {
+    let (lhs, pos) = (parsers.0.method)(input, start)?;
+    climb_from(parsers, input, lhs, pos, max, error)
+}
+
+/// Continues `lhs` with the operators from `pos` binding no looser than `max`, like `climb`.
+fn climb_from<'a>(
+    parsers: Operators<'_, 'a>,
+    input: &'a [Token],
+    mut lhs: Expr,
+    mut pos: usize,
+    max: usize,
+    error: &mut Option<(String, Option<usize>)>,
+) -> pom::Result<(Expr, usize)> {
+    let (_, op, else_) = parsers;
+    while let Ok(((name, pri, assoc), after)) = (op.method)(input, pos) {
+        let op_position = pos;
+        if pri > max {
+            break;
+        }
+        let rhs_max = match assoc {
+            Assoc::Left | Assoc::None => pri - 1,
+            Assoc::Right => pri,
+        };
+        let (rhs, end) = match climb(parsers, input, after, rhs_max, error) {
+            Ok(rhs) => rhs,
+            Err(e @ pom::Error::Expect { .. }) => return Err(e),
+            Err(_) => break,
+        };
+        pos = end;
+        if name == "?" {
+            if let Ok((_, after)) = (else_.method)(input, pos) {
+                match climb(parsers, input, after, pri, error) {
+                    Ok((otherwise, end)) => {
+                        pos = end;
+                        lhs = Expr::Operation(Box::new(Operation::IfElse(lhs, rhs, otherwise)));
+                        continue;
                     }
-                    i += on_reduce;
-                } else {
-                    i += step;
+                    Err(e @ pom::Error::Expect { .. }) => return Err(e),
+                    Err(_) => {}
                 }
             }
         }
-        first
+        if let Some((message, position)) = check_chain(input, &lhs, name, &rhs, op_position) {
+            error.get_or_insert((message, Some(position)));
+            continue;
+        }
+        lhs = reduce_binary(lhs, name, rhs).unwrap_or_else(|e| {
+            let (lhs, message) = *e;
+            error.get_or_insert((message, None));
+            lhs
+        });
+    }
+    Ok((lhs, pos))
+}
+
+/// Attaches the source range covered by the tokens consumed by `parser`.
+fn spanned<'a, O: 'a>(parser: Parser<'a, O>) -> Parser<'a, (O, Range<usize>)> {
+    pom::parser::Parser::new(move |input: &'a [Token], start| {
+        let (out, end) = (parser.method)(input, start)?;
+        let range = match (
+            input.get(start),
+            end.checked_sub(1).and_then(|i| input.get(i)),
+        ) {
+            (Some(first), Some(last)) if end > start => first.1.start..last.1.end,
+            _ => 0..0,
+        };
+        Ok(((out, range), end))
     })
 }
 
-fn is_ident<'a>() -> Parser<'a, Token> {
-    is_a(|Token(v, _)| {
-        if let TokenValue::Ident(_) = v {
-            true
-        } else {
-            false
+/// Fails with an error that is neither recovered by alternatives nor stops repetitions silently,
+/// for mistakes that no other parse could accept.
+fn fatal<'a, O: 'a>(parser: Parser<'a, Result<O, String>>) -> Parser<'a, O> {
+    fatal_at(parser.map(|result| result.map_err(|message| (message, None))))
+}
+
+/// Fails like `fatal`, at the token position given along with the message, or at the start of
+/// `parser` if none is given.
+fn fatal_at<'a, O: 'a>(parser: Parser<'a, Result<O, (String, Option<usize>)>>) -> Parser<'a, O> {
+    pom::parser::Parser::new(move |input, start| {
+        let (result, end) = (parser.method)(input, start)?;
+        result
+            .map(|out| (out, end))
+            .map_err(|(message, position)| unrecoverable(message, position.unwrap_or(start)))
+    })
+}
+
+/// The error `fatal` fails with.
+fn unrecoverable(message: String, position: usize) -> pom::Error {
+    pom::Error::Expect {
+        message,
+        position,
+        inner: Box::new(pom::Error::Custom {
+            message: "Unrecoverable syntax error.".to_string(),
+            position,
+            inner: None,
+        }),
+    }
+}
+
+/// Repeats `parser` like `repeat(..)`, but passes on errors produced by `fatal`.
+fn repeat_lines<'a, O: 'a>(parser: Parser<'a, O>) -> Parser<'a, Vec<O>> {
+    pom::parser::Parser::new(move |input, start| {
+        let mut items = Vec::new();
+        let mut pos = start;
+        loop {
+            match (parser.method)(input, pos) {
+                Ok((item, end)) => {
+                    items.push(item);
+                    pos = end;
+                }
+                Err(e @ pom::Error::Expect { .. }) => return Err(e),
+                Err(_) => return Ok((items, pos)),
+            }
         }
     })
 }
 
-fn ident<'a>(token: Token) -> Result<Ident, &'static str> {
-    if let TokenValue::Ident(result) = token.0 {
-        Ok(Ident(result, token.1))
+thread_local! {
+    /// The furthest token position any parser has looked at, which is where the source stops
+    /// making sense if it could not be parsed.
+    static FURTHEST: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Records the position `parser` looks at into `FURTHEST`.
+fn tracked<'a, O: 'a>(parser: Parser<'a, O>) -> Parser<'a, O> {
+    pom::parser::Parser::new(move |input, start| {
+        FURTHEST.with(|f| f.set(f.get().max(start)));
+        (parser.method)(input, start)
+    })
+}
+
+// The parsers of single tokens, shadowing those of pom to be tracked.
+
+fn sym<'a>(t: Token) -> Parser<'a, Token> {
+    tracked(pom::parser::sym(t))
+}
+
+fn is_a<'a>(predicate: impl Fn(Token) -> bool + 'a) -> Parser<'a, Token> {
+    tracked(pom::parser::is_a(predicate))
+}
+
+fn any<'a>() -> Parser<'a, Token> {
+    tracked(pom::parser::any())
+}
+
+/// Runs `parse` from `start`, giving along the furthest token position it looked at.
+fn furthest<T>(start: usize, parse: impl FnOnce() -> T) -> (T, usize) {
+    let outer = FURTHEST.with(|f| f.replace(start));
+    let result = parse();
+    let furthest = FURTHEST.with(|f| f.replace(outer.max(f.get())));
+    (result, furthest)
+}
+
+/// How a token is called in syntax errors, or `None` for the tokens ending a line.
+fn describe(Token(t, _): &Token) -> Option<String> {
+    Some(match t {
+        TokenValue::Ident(s) => format!("`{}`", s),
+        TokenValue::Punct(p) => format!("`{}`", p.iter().collect::<String>()),
+        TokenValue::Keyword(k) => format!("`{}`", k),
+        TokenValue::Label(s) => format!("`'{}`", s),
+        TokenValue::Doc(_) => "doc comment".to_string(),
+        TokenValue::LitStr(s) => format!("`{:?}`", s),
+        TokenValue::LitNum(n) => format!("`{}`", n),
+        TokenValue::BeginBlock => "indented block".to_string(),
+        _ => return None,
+    })
+}
+
+/// Reports the token at `pos` that could not be parsed, or the end of the line before it.
+fn unexpected(input: &[Token], pos: usize) -> Diagnostic {
+    if let Some(name) = input.get(pos).and_then(describe) {
+        return Diagnostic::new(format!("Unexpected {}.", name), input[pos].1.clone());
+    }
+    let last = input[..pos.min(input.len())]
+        .iter()
+        .rev()
+        .find_map(|t| describe(t).map(|name| (name, t.1.clone())));
+    match last {
+        Some((name, range)) => {
+            Diagnostic::new(format!("The line ends unexpectedly after {}.", name), range)
+        }
+        None => Diagnostic::new("Unexpected end of input.", 0..0),
+    }
+}
+
+fn is_ident<'a>() -> Parser<'a, Token> {
+    is_a(|Token(v, _)| matches!(v, TokenValue::Ident(_)))
+}
+
+fn ident(token: Token) -> Result<Ident, &'static str> {
+    if let TokenValue::Ident(name) = token.0 {
+        Ok(Ident {
+            name,
+            range: token.1,
+        })
     } else {
         Err("")
     }
 }
 
-pub fn punct<'a>(v: &'static str) -> Parser<'a, Token> {
+fn punct<'a>(v: &'static str) -> Parser<'a, Token> {
     sym(TokenValue::Punct(puncts(v)).token())
 }
 
+fn keyword<'a>(v: &'static str) -> Parser<'a, Token> {
+    sym(TokenValue::Keyword(v).token())
+}
+
 fn var_def<'a>() -> Parser<'a, VarDef> {
-    let expr = any() + (punct(":") * any()).opt();
-    expr.convert::<_, &'static str, _>(|(n, t)| {
-        Ok(if let Some(t) = t {
-            VarDef(ident(n)?, Some(ident(t)?))
-        } else {
-            VarDef(ident(n)?, None)
+    let expr = is_ident() + (punct(":") * type_expr()).opt();
+    expr.convert::<_, &'static str, _>(|(name, ty)| {
+        Ok(VarDef {
+            name: ident(name)?,
+            ty,
         })
     })
 }
 
 fn var<'a>() -> Parser<'a, Expr> {
-    var_def().map(|d| Expr::Var(d))
+    var_def().map(Expr::Var)
 }
 
 fn non_left_recursive<'a>() -> Parser<'a, Expr> {
-    func() | var() | last_line() | lit_number() | lit_string() | call(block)
+    struct_decl()
+        | enum_decl()
+        | type_alias()
+        | match_expr()
+        | jump()
+        | import()
+        | export()
+        | declaration()
+        | labeled()
+        | func()
+        | call_expr()
+        | for_each()
+        | last_line()
+        | lit_number()
+        | lit_string()
+        | call(block)
 }
 
-pub fn func<'a>() -> Parser<'a, Expr> {
-    let args = punct("(") * var_def().repeat(..) - punct(")");
-    let ret_type = (punct(":") * is_ident()).opt();
+/// Parameters in a multiline list could be documented by doc comments before them, and doc comments
+/// after the last parameter document the returned value.
+fn func<'a>() -> Parser<'a, Expr> {
+    let args = params_ahead() * documented_separated("(", expr, ")");
+    // The `=>` of the function itself would be taken by a function type, so a returned function
+    // type must be parenthesized.
+    let ret_type = (punct(":") * union_type(false)).opt();
     let body = punct("=>") * call(expr);
-    (args + ret_type + body).convert::<_, &'static str, _>(|((args, ret_type), body)| {
-        Ok(Expr::Func(Box::new((
-            args,
-            if let Some(x) = ret_type {
-                Some(ident(x)?)
-            } else {
-                None
-            },
-            body,
-        ))))
+    spanned(args + ret_type + body).convert::<_, &'static str, _>(
+        |((((_, args, ret_doc, _), ret_type), body), range)| {
+            let docs = args
+                .iter()
+                .map(|(doc, _, _)| doc.clone())
+                .collect::<Vec<_>>();
+            let args = args
+                .into_iter()
+                .map(|(_, arg, comma)| (arg, comma))
+                .collect();
+            let args = comma_separated(args)?.0.into_iter().zip(docs);
+            let args = args
+                .map(|(arg, doc)| pattern(arg).map(|arg| Param { pattern: arg, doc }))
+                .collect::<Result<_, _>>()
+                .map_err(|_| "")?;
+            Ok(Expr::Func(
+                Box::new(Func {
+                    params: args,
+                    ret_type,
+                    body,
+                    ret_doc,
+                }),
+                range,
+            ))
+        },
+    )
+}
+
+/// Looks ahead for the `)` matching the `(` at the start, followed by the `:` or `=>` of a function,
+/// so that parenthesized expressions and tuples are not parsed as parameters before as themselves.
+fn params_ahead<'a>() -> Parser<'a, ()> {
+    pom::parser::Parser::new(|input: &'a [Token], start| {
+        match matching_bracket(input, start, "(") {
+            Some(close)
+                if is_punct(input.get(close + 1), "=>") || is_punct(input.get(close + 1), ":") =>
+            {
+                Ok(((), start))
+            }
+            _ => Err(pom::Error::Mismatch {
+                message: "expect parameters".to_string(),
+                position: start,
+            }),
+        }
     })
 }
 
+fn is_punct(token: Option<&Token>, v: &str) -> bool {
+    matches!(token, Some(Token(TokenValue::Punct(p), _)) if *p == puncts(v))
+}
+
+/// The position of the bracket closing the `open` bracket at `start`, counting all kinds of brackets.
+fn matching_bracket(input: &[Token], start: usize, open: &str) -> Option<usize> {
+    if !is_punct(input.get(start), open) {
+        return None;
+    }
+    let mut depth = 0;
+    for (i, token) in input.iter().enumerate().skip(start) {
+        if ["(", "{", "["].iter().any(|b| is_punct(Some(token), b)) {
+            depth += 1;
+        } else if [")", "}", "]"].iter().any(|b| is_punct(Some(token), b)) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+fn type_expr<'a>() -> Parser<'a, TypeExpr> {
+    union_type(true)
+}
+
+fn union_type<'a>(allow_func: bool) -> Parser<'a, TypeExpr> {
+    let member = move || optional_type(allow_func);
+    (member() + (punct("|") * member()).repeat(..)).map(|(first, rest)| {
+        if rest.is_empty() {
+            first
+        } else {
+            TypeExpr::Union(Some(first).into_iter().chain(rest).collect())
+        }
+    })
+}
+
+/// `?T` is a shorthand of `T | ()`, it is written as a prefix to keep it apart from the conditional
+/// operator. `!T` is the type of thrown values, written as a prefix alike.
+fn optional_type<'a>(allow_func: bool) -> Parser<'a, TypeExpr> {
+    let prefix = punct("?") | punct("!");
+    let prefixed = prefix + call(move || optional_type(allow_func));
+    prefixed.map(|(prefix, t)| {
+        let range = prefix.1.start..t.span().end;
+        if prefix.0 == TokenValue::Punct(puncts("?")) {
+            TypeExpr::Optional(Box::new(t), range)
+        } else {
+            TypeExpr::Thrown(Box::new(t), range)
+        }
+    }) | primary_type(allow_func)
+}
+
+fn primary_type<'a>(allow_func: bool) -> Parser<'a, TypeExpr> {
+    let name = is_ident() + (punct("<") + list(call(type_expr), punct(",")) + punct(">")).opt();
+    let name = name.convert::<_, &'static str, _>(|(name, args)| {
+        let name = ident(name)?;
+        Ok(match args {
+            Some(((_, args), close)) if !args.is_empty() => {
+                let range = name.range.start..close.1.end;
+                TypeExpr::Generic(name, args, range)
+            }
+            Some(_) => return Err(""),
+            None => TypeExpr::Name(name),
+        })
+    });
+    let array = (punct("{") + call(type_expr) + punct("}"))
+        .map(|((open, t), close)| TypeExpr::Array(Box::new(t), open.1.start..close.1.end));
+    let ret_type = if allow_func {
+        (punct("=>") * call(type_expr)).opt()
+    } else {
+        empty().map(|_| None)
+    };
+    let parens = separated("(", type_expr, ")") + ret_type;
+    let parens = parens.convert::<_, &'static str, _>(|((open, types, close), ret)| {
+        let (mut types, has_comma) = comma_separated(types)?;
+        Ok(match ret {
+            Some(ret) => {
+                let range = open.1.start..ret.span().end;
+                TypeExpr::Func(Box::new((types, ret)), range)
+            }
+            None if types.is_empty() => TypeExpr::Unit(open.1.start..close.1.end),
+            None if types.len() == 1 && !has_comma => types.pop().unwrap(),
+            None => TypeExpr::Tuple(types, open.1.start..close.1.end),
+        })
+    });
+    name | array | parens
+}
+
+fn type_params<'a>() -> Parser<'a, Vec<Ident>> {
+    let params = punct("<") * list(is_ident(), punct(",")) - punct(">");
+    params
+        .opt()
+        .convert::<_, &'static str, _>(|p| p.unwrap_or_default().into_iter().map(ident).collect())
+}
+
+/// Parses the lines of an indented block with `item`, which is empty if no block is following.
+fn decl_body<'a, O: 'a>(item: fn() -> Parser<'a, O>) -> Parser<'a, Vec<O>> {
+    let body = sym(TokenValue::BeginBlock.token())
+        * (sym(TokenValue::Line(0).token()) * call(item)).repeat(..)
+        - sym(TokenValue::EndBlock.token());
+    body.opt().map(Option::unwrap_or_default)
+}
+
+fn field_def<'a>() -> Parser<'a, FieldDef> {
+    (is_ident() - punct(":") + type_expr()).convert::<_, &'static str, _>(|(name, ty)| {
+        Ok(FieldDef {
+            name: ident(name)?,
+            ty,
+        })
+    })
+}
+
+fn variant<'a>() -> Parser<'a, Variant> {
+    let payload = separated("(", type_expr, ")")
+        .convert(|(_, types, close)| Ok::<_, &'static str>((comma_separated(types)?.0, close)));
+    (is_ident() + payload.opt()).convert::<_, &'static str, _>(|(name, payload)| {
+        let (payload, end) =
+            payload.map_or((Vec::new(), name.1.end), |(p, close)| (p, close.1.end));
+        let range = name.1.start..end;
+        Ok(Variant {
+            name: ident(name)?,
+            payload,
+            range,
+        })
+    })
+}
+
+fn struct_decl<'a>() -> Parser<'a, Expr> {
+    let decl = keyword("struct") + is_ident() + type_params() + decl_body(field_def);
+    decl.convert::<_, &'static str, _>(|(((kw, name), type_params), fields)| {
+        let end = fields.last().map_or(name.1.end, |f| f.ty.span().end);
+        let range = kw.1.start..end;
+        Ok(Expr::Struct(Box::new(StructDecl {
+            name: ident(name)?,
+            type_params,
+            fields,
+            range,
+        })))
+    })
+}
+
+fn enum_decl<'a>() -> Parser<'a, Expr> {
+    let decl = keyword("enum") + is_ident() + type_params() + decl_body(variant);
+    decl.convert::<_, &'static str, _>(|(((kw, name), type_params), variants)| {
+        let end = variants.last().map_or(name.1.end, |v| v.range.end);
+        let range = kw.1.start..end;
+        Ok(Expr::Enum(Box::new(EnumDecl {
+            name: ident(name)?,
+            type_params,
+            variants,
+            range,
+        })))
+    })
+}
+
+fn type_alias<'a>() -> Parser<'a, Expr> {
+    let decl = keyword("type") + is_ident() + type_params() - punct("=") + type_expr();
+    decl.convert::<_, &'static str, _>(|(((kw, name), type_params), ty)| {
+        let range = kw.1.start..ty.span().end;
+        Ok(Expr::TypeAlias(Box::new(TypeAlias {
+            name: ident(name)?,
+            type_params,
+            ty,
+            range,
+        })))
+    })
+}
+
+/// `match` arms are written in an indented block, each with a pattern, an optional `if` guard and
+/// the resulting expression after `=>`.
+fn match_expr<'a>() -> Parser<'a, Expr> {
+    let arms = sym(TokenValue::BeginBlock.token())
+        * repeat_lines(sym(TokenValue::Line(0).token()) * call(arm))
+        - sym(TokenValue::EndBlock.token());
+    spanned(keyword("match") * call(expr) + arms)
+        .map(|((value, arms), range)| Expr::Match(Box::new(Match { value, arms, range })))
+}
+
+fn arm<'a>() -> Parser<'a, Arm> {
+    // Functions are left out, since `(a, b) => ...` is the arm itself.
+    let pattern = call_expr()
+        | call(record)
+        | var()
+        | lit_number()
+        | lit_string()
+        | call(tuple)
+        | call(array);
+    let pattern =
+        fatal(pattern.map(|e| match_pattern(e).map_err(|e| format!("Invalid pattern `{:?}`.", e))));
+    let guard = (keyword("if") * call(expr)).opt();
+    (pattern + guard - punct("=>") + call(expr)).map(|((pattern, guard), body)| Arm {
+        pattern,
+        guard,
+        body,
+    })
+}
+
+/// `break` and `return` take an optional value from the rest of the line, while `break` and
+/// `continue` could be given the label of the loop to jump out of.
+fn jump<'a>() -> Parser<'a, Expr> {
+    let value = || call(expr).map(Box::new).opt();
+    let brk = spanned(keyword("break") * label().opt() + value())
+        .map(|((label, value), range)| Expr::Break(label, value, range));
+    let cont = spanned(keyword("continue") * label().opt())
+        .map(|(label, range)| Expr::Continue(label, range));
+    let ret = spanned(keyword("return") * value()).map(|(value, range)| Expr::Return(value, range));
+    let throw = spanned(keyword("throw") * call(expr).map(Box::new))
+        .map(|(value, range)| Expr::Throw(value, range));
+    brk | cont | ret | throw
+}
+
+fn import<'a>() -> Parser<'a, Expr> {
+    let path = any().convert(|Token(t, _)| match t {
+        TokenValue::LitStr(s) => Ok(s),
+        _ => Err("expect module path"),
+    });
+    spanned(keyword("import") * path).map(|(path, range)| Expr::Import(path, range))
+}
+
+/// Exports a declaration from the module.
+fn export<'a>() -> Parser<'a, Expr> {
+    fatal(
+        spanned(keyword("export") * call(expr)).map(|(e, range)| match e {
+            e @ (Expr::Let(..) | Expr::Struct(_) | Expr::Enum(_) | Expr::TypeAlias(_)) => {
+                Ok(Expr::Export(Box::new(e), range))
+            }
+            _ => Err("Only declarations could be exported.".to_string()),
+        }),
+    )
+}
+
+/// `let` and `const` are followed by an assignment, whose target is the pattern to declare.
+fn declaration<'a>() -> Parser<'a, Expr> {
+    let kind = keyword("let").map(|_| false) | keyword("const").map(|_| true);
+    // An `=` left after the pattern means its value could not be parsed, which is reported as is.
+    fatal(
+        (spanned(kind + call(expr)) - !punct("=")).map(|((constant, e), range)| {
+            if let Expr::Operation(o) = e {
+                if let Operation::Assign(pattern, value) = *o {
+                    let decl = Let {
+                        pattern,
+                        value,
+                        constant,
+                    };
+                    return Ok(Expr::Let(Box::new(decl), range));
+                }
+            }
+            let kind = if constant { "const" } else { "let" };
+            Err(format!("`{}` must be followed by an assignment.", kind))
+        }),
+    )
+}
+
+fn label<'a>() -> Parser<'a, Ident> {
+    any().convert(|Token(t, range)| match t {
+        TokenValue::Label(name) => Ok(Ident { name, range }),
+        _ => Err(""),
+    })
+}
+
+/// For-each loops are written as `pattern in iterable ^ body`, where the rest after `in` is parsed
+/// as a `^` or `^^` loop whose condition is taken as the iterable. The operands that could be a
+/// pattern are parsed here once, and given back as they are if no `in` follows.
+fn for_each<'a>() -> Parser<'a, Expr> {
+    let operand = call(record) | var() | call(tuple) | call(array);
+    let for_each = operand + (keyword("in") * call(expr)).opt();
+    fatal(for_each.map(|(operand, e)| {
+        let e = match e {
+            Some(e) => e,
+            None => return Ok(operand),
+        };
+        let pattern =
+            pattern(operand).map_err(|p| format!("Invalid pattern `{:?}` before `in`.", p))?;
+        match e {
+            Expr::Operation(o) => match *o {
+                Operation::While(iter, body, None) => {
+                    Ok(Operation::ForEach(pattern, iter, body, None))
+                }
+                Operation::Generator(iter, body, None) => {
+                    Ok(Operation::ForEachGenerator(pattern, iter, body, None))
+                }
+                _ => Err("`in` must be followed by a loop.".to_string()),
+            },
+            _ => Err("`in` must be followed by a loop.".to_string()),
+        }
+        .map(|o| Expr::Operation(Box::new(o)))
+    }))
+}
+
+/// A label written before a loop as `'label: condition ^ body`.
+fn labeled<'a>() -> Parser<'a, Expr> {
+    let labeled = label() - punct(":") + call(expr);
+    fatal(labeled.map(|(label, e)| match e {
+        Expr::Operation(mut o) => match o.as_mut() {
+            Operation::While(_, _, l)
+            | Operation::Generator(_, _, l)
+            | Operation::ForEach(_, _, _, l)
+            | Operation::ForEachGenerator(_, _, _, l) => {
+                *l = Some(label);
+                Ok(Expr::Operation(o))
+            }
+            _ => Err(format!(
+                "Label `'{}` is not followed by a loop.",
+                label.name
+            )),
+        },
+        _ => Err(format!(
+            "Label `'{}` is not followed by a loop.",
+            label.name
+        )),
+    }))
+}
+
 fn last_line<'a>() -> Parser<'a, Expr> {
     punct("@").map(|Token(_, range)| Expr::LastLine(range))
 }
@@ -251,32 +1139,498 @@ fn expr<'a>() -> Parser<'a, Expr> {
 }
 
 fn indent_block<'a>() -> Parser<'a, Expr> {
-    (sym(TokenValue::BeginBlock.token()) * ml_block() - sym(TokenValue::EndBlock.token()))
-        .map(|b| Expr::Block(b))
+    // Spans the lines only, without the line breaks and the indents before them.
+    (sym(TokenValue::BeginBlock.token()) * ml_block() - sym(TokenValue::EndBlock.token())).map(
+        |block| {
+            let range = block.span();
+            Expr::Block(block, range)
+        },
+    )
 }
 
 fn block<'a>() -> Parser<'a, Expr> {
-    let bracketed_block = punct("{") * sym(TokenValue::BeginBlock.token()) * ml_block()
-        - sym(TokenValue::EndBlock.token())
-        - sym(TokenValue::Line(0).token())
-        - punct("}");
+    let bracketed_block =
+        punct("{") * sym(TokenValue::BeginBlock.token()) * ml_block().map(|b| b.lines)
+            - sym(TokenValue::EndBlock.token())
+            - sym(TokenValue::Line(0).token())
+            - punct("}");
     let inline_block = punct("{")
-        * (((expr() - punct(";")).repeat(1..) + expr().opt()).map(|(mut v, o)| {
-            if let Some(o) = o {
-                v.push(o);
-            }
-            v
-        }) | expr().map(|e| vec![e]))
-        .map(|v| Block(v))
+        * (expr() + (punct(";") * expr()).repeat(..)).map(|(first, mut rest)| {
+            rest.insert(0, first);
+            rest
+        })
+        - punct(";").opt()
         - punct("}");
-    indent_block() | (bracketed_block | inline_block).map(|b| Expr::Block(b))
+    indent_block()
+        | spanned(bracketed_block | inline_block).map(|(lines, range)| {
+            let block = Block {
+                lines,
+                range: range.clone(),
+            };
+            Expr::Block(block, range)
+        })
 }
 
+/// Brackets and the items inside, each with whether it is followed by a `,`.
+type Separated<O> = (Token, Vec<(O, bool)>, Token);
+
+/// Parses items divided by `,` inside a pair of brackets, either inline or expanded into an indented
+/// block of lines. Each item is returned along with whether it is followed by a `,`.
+fn separated<'a, O: 'a>(
+    open: &'static str,
+    item: fn() -> Parser<'a, O>,
+    close: &'static str,
+) -> Parser<'a, Separated<O>> {
+    documented_separated(open, item, close).map(|(open, items, _, close)| {
+        let items = items.into_iter().map(|(_, item, comma)| (item, comma));
+        (open, items.collect(), close)
+    })
+}
+
+type Documented<O> = (Token, Vec<(Option<String>, O, bool)>, Option<String>, Token);
+
+/// Parses items like `separated`, where the lines of a multiline list could also be doc comments.
+/// Each item is returned along with the doc comment before it, and the doc comment after the last
+/// item is returned after the items.
+fn documented_separated<'a, O: 'a>(
+    open: &'static str,
+    item: fn() -> Parser<'a, O>,
+    close: &'static str,
+) -> Parser<'a, Documented<O>> {
+    let element = move || call(item) + punct(",").opt().map(|c| c.is_some());
+    let line = docs() + sym(TokenValue::Line(0).token()) * element().repeat(1..);
+    let lines = line.repeat(1..).map(|lines| {
+        let lines = lines.into_iter().flat_map(|(doc, elements)| {
+            let docs = Some(doc).into_iter().chain(std::iter::repeat(None));
+            docs.zip(elements).map(|(doc, (e, comma))| (doc, e, comma))
+        });
+        lines.collect()
+    });
+    let multiline = sym(TokenValue::BeginBlock.token()) * lines + docs()
+        - sym(TokenValue::EndBlock.token())
+        - sym(TokenValue::Line(0).token());
+    let inline = element()
+        .repeat(..)
+        .map(|items| items.into_iter().map(|(e, comma)| (None, e, comma)));
+    (punct(open) + (multiline | inline.map(|items| (items.collect(), None))) + punct(close))
+        .map(|((open, (items, doc)), close)| (open, items, doc, close))
+}
+
+/// Lines of doc comments, joined by line breaks.
+fn docs<'a>() -> Parser<'a, Option<String>> {
+    let doc = any().convert(|Token(t, _)| match t {
+        TokenValue::Doc(doc) => Ok(doc),
+        _ => Err(""),
+    });
+    (sym(TokenValue::Line(0).token()) * doc)
+        .repeat(..)
+        .map(|docs| Some(docs.join("\n")).filter(|_| !docs.is_empty()))
+}
+
+/// Checks that every item but the last is followed by a `,`, returning the items and whether any
+/// `,` is present at all.
+fn comma_separated<O>(items: Vec<(O, bool)>) -> Result<(Vec<O>, bool), &'static str> {
+    if items.iter().rev().skip(1).all(|(_, comma)| *comma) {
+        let has_comma = items.iter().any(|(_, comma)| *comma);
+        Ok((items.into_iter().map(|(i, _)| i).collect(), has_comma))
+    } else {
+        Err("")
+    }
+}
+
+/// Arrays are told apart from blocks by their separators: elements are divided by `,`, and an array
+/// must contain at least one `,` unless it is empty, so `{ x, }` is a single-element array while
+/// `{ x }` stays an inline block.
+fn array<'a>() -> Parser<'a, Expr> {
+    array_ahead()
+        * separated("{", array_item, "}").convert(|(open, elements, close)| {
+            let (elements, has_comma) = comma_separated(elements)?;
+            if has_comma || elements.is_empty() {
+                Ok(Expr::Array(elements, open.1.start..close.1.end))
+            } else {
+                Err("")
+            }
+        })
+}
+
+/// Looks ahead for a `,` directly inside the `{` at the start, or for nothing inside, so that blocks
+/// are not parsed as arrays before as themselves.
+fn array_ahead<'a>() -> Parser<'a, ()> {
+    pom::parser::Parser::new(|input: &'a [Token], start| {
+        let close = matching_bracket(input, start, "{").ok_or(pom::Error::Mismatch {
+            message: "expect array".to_string(),
+            position: start,
+        })?;
+        let mut depth = 0;
+        let mut empty = true;
+        for token in &input[start + 1..close] {
+            if depth == 0 && is_punct(Some(token), ",") {
+                return Ok(((), start));
+            }
+            if ["(", "{", "["].iter().any(|b| is_punct(Some(token), b)) {
+                depth += 1;
+            } else if [")", "}", "]"].iter().any(|b| is_punct(Some(token), b)) {
+                depth -= 1;
+            }
+            empty &= matches!(
+                token.0,
+                TokenValue::Line(_) | TokenValue::BeginBlock | TokenValue::EndBlock
+            );
+        }
+        if empty {
+            Ok(((), start))
+        } else {
+            Err(pom::Error::Mismatch {
+                message: "expect array".to_string(),
+                position: start,
+            })
+        }
+    })
+}
+
+fn array_item<'a>() -> Parser<'a, Expr> {
+    let spread = spanned(punct("...") * expr());
+    spread.map(|(e, range)| Expr::Spread(Box::new(e), range)) | expr()
+}
+
+/// `()` is the unit value, `(x)` is a parenthesized expression, while a `,` makes a tuple, so that
+/// a single-element tuple is written as `(x,)`.
+fn tuple<'a>() -> Parser<'a, Expr> {
+    separated("(", expr, ")").convert::<_, &'static str, _>(|(open, elements, close)| {
+        let (mut elements, has_comma) = comma_separated(elements)?;
+        let range = open.1.start..close.1.end;
+        Ok(if elements.len() == 1 && !has_comma {
+            Expr::Paren(Box::new(elements.pop().unwrap()), range)
+        } else {
+            Expr::Tuple(elements, range)
+        })
+    })
+}
+
+fn field<'a>() -> Parser<'a, Field> {
+    (is_ident() + (punct(":") * expr()).opt()).convert::<_, &'static str, _>(|(name, value)| {
+        Ok(Field {
+            name: ident(name)?,
+            value,
+        })
+    })
+}
+
+/// Records are prefixed with a `.` to keep them apart from blocks and arrays, which could be preceded
+/// by the name of a struct type.
+fn record<'a>() -> Parser<'a, Expr> {
+    (is_ident().opt() + punct(".") + separated("{", field, "}")).convert::<_, &'static str, _>(
+        |((name, dot), (_, fields, close))| {
+            let (fields, _) = comma_separated(fields)?;
+            let start = name.as_ref().map_or(dot.1.start, |n| n.1.start);
+            let name = match name {
+                Some(name) => Some(ident(name)?),
+                None => None,
+            };
+            Ok(Expr::Record(name, fields, start..close.1.end))
+        },
+    )
+}
+
+fn call_expr<'a>() -> Parser<'a, Expr> {
+    spanned(is_ident() + separated("(", arg, ")")).convert::<_, &'static str, _>(
+        |((name, (_, args, _)), range)| {
+            Ok(Expr::Call(ident(name)?, comma_separated(args)?.0, range))
+        },
+    )
+}
+
+/// `name: value` in a call is always a named argument, rather than a variable with a type.
+fn arg<'a>() -> Parser<'a, Arg> {
+    let named = is_ident().convert(ident) - punct(":") + expr();
+    let spread = spanned(punct("...") * expr());
+    named.map(|(name, e)| Arg::Named(name, e))
+        | spread.map(|(e, range)| Arg::Spread(e, range))
+        | expr().map(Arg::Positional)
+}
+
+fn attribute<'a>() -> Parser<'a, Attribute> {
+    let args = separated("(", expr, ")")
+        .convert(|(_, args, _)| comma_separated(args).map(|(args, _)| args));
+    let attribute = punct("#") * punct("[") * is_ident().convert(ident) + args.opt() - punct("]");
+    let attribute = spanned(attribute).map(|((name, args), range)| Attribute {
+        name,
+        args: args.unwrap_or_default(),
+        range,
+    });
+    // A line of doc comment is the same as `#[doc("...")]`.
+    let doc = any().convert(|Token(t, range)| match t {
+        TokenValue::Doc(doc) => {
+            let name = Ident {
+                name: "doc".to_string(),
+                range: range.clone(),
+            };
+            Ok(Attribute {
+                name,
+                args: vec![Expr::LitStr(doc, range.clone())],
+                range,
+            })
+        }
+        _ => Err(""),
+    });
+    attribute | doc
+}
+
+/// Attributes and doc comments are written on the lines before the expression they are attached
+/// to.
+fn attributed<'a>() -> Parser<'a, Expr> {
+    let line = || sym(TokenValue::Line(0).token());
+    let attributes = attribute() + (line() * attribute()).repeat(..);
+    let attributed = attributes + (line() * call(expr)).opt();
+    fatal(attributed.map(|((first, mut rest), e)| match e {
+        Some(e) => {
+            rest.insert(0, first);
+            Ok(Expr::Attributed(rest, Box::new(e)))
+        }
+        None => Err("Attributes must be followed by an expression.".to_string()),
+    }))
+}
+
+/// The lines of a block. A line that could not be parsed is replaced by an `Expr::Error`, and
+/// parsing resumes from the next line of the block, so that one mistake does not hide the rest.
 fn ml_block<'a>() -> Parser<'a, Block> {
-    let block = (sym(TokenValue::Line(0).token()) * expr()).repeat(..);
-    block.map(|v| Block(v))
+    let line = attributed() | expr();
+    let pipe_lines = pipe_lines();
+    pom::parser::Parser::new(move |input: &'a [Token], start| {
+        let mut lines = Vec::new();
+        let mut pos = start;
+        while let Some(Token(TokenValue::Line(_), _)) = input.get(pos) {
+            let (result, furthest) = furthest(pos + 1, || {
+                let (e, end) = (line.method)(input, pos + 1)?;
+                pipe_lines(e, input, end)
+            });
+            // Looking ahead for `!?` could reach the next line, which is not to blame.
+            let mut next = next_line(input, pos + 1);
+            while let (Some(Token(TokenValue::Line(_), _)), true) =
+                (input.get(next), is_punct(input.get(next + 1), "|>"))
+            {
+                next = next_line(input, next + 1);
+            }
+            let error = match result {
+                Ok((e, end)) => match input.get(end) {
+                    None | Some(Token(TokenValue::Line(_) | TokenValue::EndBlock, _)) => {
+                        lines.push(e);
+                        pos = end;
+                        continue;
+                    }
+                    Some(_) => unexpected(input, furthest.min(next).max(end)),
+                },
+                Err(e @ pom::Error::Expect { .. }) => Diagnostic::from_tokens_error(e, input),
+                Err(_) => unexpected(input, furthest.min(next)),
+            };
+            let mut line = input[pos..next]
+                .iter()
+                .filter(|t| t.1.start < t.1.end && !matches!(t.0, TokenValue::Line(_)));
+            let span = match (line.clone().next(), line.next_back()) {
+                (Some(first), Some(last)) => first.1.start..last.1.end,
+                _ => error.range.clone(),
+            };
+            lines.push(Expr::Error(error.message, error.range, span));
+            pos = next;
+        }
+        // Spans the lines only, or is empty at where the block would be if there is no line.
+        let at = input.get(start).or(input.last()).map_or(0, |t| t.1.start);
+        let range = match (lines.first(), lines.last()) {
+            (Some(first), Some(last)) => first.span().start..last.span().end,
+            _ => at..at,
+        };
+        Ok((Block { lines, range }, pos))
+    })
+}
+
+/// The position of the next line in the block the token at `pos` is in, or of the end of the
+/// block.
+fn next_line(input: &[Token], mut pos: usize) -> usize {
+    let mut depth = 0;
+    while let Some(Token(t, _)) = input.get(pos) {
+        match t {
+            TokenValue::BeginBlock => depth += 1,
+            TokenValue::EndBlock if depth == 0 => break,
+            TokenValue::EndBlock => depth -= 1,
+            TokenValue::Line(_) if depth == 0 => break,
+            _ => {}
+        }
+        pos += 1;
+    }
+    pos
+}
+
+/// Parses `tokens` into a program as far as possible. The syntax errors are left in the program as
+/// `Expr::Error`, and are reported along with jumps without a target.
+pub fn parse(tokens: &[Token]) -> (Block, Vec<Diagnostic>) {
+    let block = match fragment(tokens, ml_block()) {
+        Ok(block) => block,
+        Err(diagnostics) => {
+            let block = Block {
+                lines: Vec::new(),
+                range: 0..0,
+            };
+            return (block, diagnostics);
+        }
+    };
+    let mut diagnostics = Vec::new();
+    for e in &block.lines {
+        check_errors(e, &mut diagnostics);
+        check_jumps(e, &Jumps::default(), &mut diagnostics);
+        check_exports(e.unattributed(), &mut diagnostics);
+    }
+    (block, diagnostics)
+}
+
+/// Parses `tokens` as a single expression. Fragments could be parts of a loop or a function, so
+/// jumps are not checked.
+pub fn parse_expr(tokens: &[Token]) -> Result<Expr, Vec<Diagnostic>> {
+    let e = fragment(tokens, sym(TokenValue::Line(0).token()) * expr())?;
+    without_errors(e, check_errors)
+}
+
+pub fn parse_type(tokens: &[Token]) -> Result<TypeExpr, Vec<Diagnostic>> {
+    fragment(tokens, sym(TokenValue::Line(0).token()) * type_expr())
 }
 
-pub fn parser<'a>() -> Parser<'a, Block> {
-    ml_block() - end()
+/// Parses `tokens` as the lines of a block, without checking jumps like `parse_expr`.
+pub fn parse_block(tokens: &[Token]) -> Result<Block, Vec<Diagnostic>> {
+    let block = fragment(tokens, ml_block())?;
+    without_errors(block, |block, diagnostics| {
+        for e in &block.lines {
+            check_errors(e, diagnostics);
+        }
+    })
+}
+
+fn fragment<'a, O: 'a>(tokens: &'a [Token], parser: Parser<'a, O>) -> Result<O, Vec<Diagnostic>> {
+    let (result, furthest) = furthest(0, || (parser.method)(tokens, 0));
+    match result {
+        Ok((out, end)) if end == tokens.len() => Ok(out),
+        Ok((_, end)) => Err(vec![unexpected(tokens, furthest.max(end))]),
+        Err(e @ pom::Error::Expect { .. }) => Err(vec![Diagnostic::from_tokens_error(e, tokens)]),
+        Err(_) => Err(vec![unexpected(tokens, furthest)]),
+    }
+}
+
+fn without_errors<T>(
+    node: T,
+    check: impl FnOnce(&T, &mut Vec<Diagnostic>),
+) -> Result<T, Vec<Diagnostic>> {
+    let mut diagnostics = Vec::new();
+    check(&node, &mut diagnostics);
+    if diagnostics.is_empty() {
+        Ok(node)
+    } else {
+        Err(diagnostics)
+    }
+}
+
+fn check_errors(e: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+    if let Expr::Error(message, range, _) = e {
+        diagnostics.push(Diagnostic::new(message.clone(), range.clone()));
+    }
+    for child in e.children() {
+        check_errors(child, diagnostics);
+    }
+}
+
+/// Reports the exports inside `e`, which are only allowed at the top level of a module.
+fn check_exports(e: &Expr, diagnostics: &mut Vec<Diagnostic>) {
+    for child in e.children() {
+        if let Expr::Export(_, range) = child {
+            diagnostics.push(Diagnostic::new(
+                "`export` is only allowed at the top level of a module.",
+                range.clone(),
+            ));
+        }
+        check_exports(child, diagnostics);
+    }
+}
+
+/// Where `break`, `continue` and `return` could jump to.
+#[derive(Clone, Default)]
+struct Jumps<'a> {
+    /// The labels of the enclosing loops in the current function, from the outermost.
+    loops: Vec<Option<&'a Ident>>,
+    in_func: bool,
+}
+
+impl<'a> Jumps<'a> {
+    fn check_label(&self, label: &Option<Ident>, diagnostics: &mut Vec<Diagnostic>) {
+        if let Some(Ident { name, range }) = label {
+            if !self.loops.iter().flatten().any(|l| &l.name == name) {
+                diagnostics.push(Diagnostic::new(
+                    format!("Unknown label `'{}`.", name),
+                    range.clone(),
+                ));
+            }
+        }
+    }
+
+    fn enter_loop(&self, label: &'a Option<Ident>, diagnostics: &mut Vec<Diagnostic>) -> Self {
+        if let Some(Ident { name, range }) = label {
+            if self.loops.iter().flatten().any(|l| &l.name == name) {
+                diagnostics.push(Diagnostic::new(
+                    format!("Label `'{}` shadows the label of an enclosing loop.", name),
+                    range.clone(),
+                ));
+            }
+        }
+        let mut jumps = self.clone();
+        jumps.loops.push(label.as_ref());
+        jumps
+    }
+}
+
+fn check_jumps<'a>(e: &'a Expr, jumps: &Jumps<'a>, diagnostics: &mut Vec<Diagnostic>) {
+    match e {
+        Expr::Break(_, _, range) if jumps.loops.is_empty() => {
+            diagnostics.push(Diagnostic::new("`break` outside of a loop.", range.clone()));
+        }
+        Expr::Continue(_, range) if jumps.loops.is_empty() => {
+            diagnostics.push(Diagnostic::new(
+                "`continue` outside of a loop.",
+                range.clone(),
+            ));
+        }
+        Expr::Break(label, _, _) | Expr::Continue(label, _) => {
+            jumps.check_label(label, diagnostics);
+        }
+        Expr::Return(_, range) if !jumps.in_func => {
+            diagnostics.push(Diagnostic::new(
+                "`return` outside of a function.",
+                range.clone(),
+            ));
+        }
+        _ => {}
+    }
+    match e {
+        Expr::Func(func, _) => {
+            let jumps = Jumps {
+                loops: Vec::new(),
+                in_func: true,
+            };
+            check_jumps(&func.body, &jumps, diagnostics);
+        }
+        Expr::Operation(o) => match o.as_ref() {
+            Operation::While(c, body, label)
+            | Operation::Generator(c, body, label)
+            | Operation::ForEach(_, c, body, label)
+            | Operation::ForEachGenerator(_, c, body, label) => {
+                check_jumps(c, jumps, diagnostics);
+                check_jumps(body, &jumps.enter_loop(label, diagnostics), diagnostics);
+            }
+            _ => {
+                for child in e.children() {
+                    check_jumps(child, jumps, diagnostics);
+                }
+            }
+        },
+        _ => {
+            for child in e.children() {
+                check_jumps(child, jumps, diagnostics);
+            }
+        }
+    }
 }
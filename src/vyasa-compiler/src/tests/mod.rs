@@ -1,4 +1,13 @@
-use crate::compile;
+use crate::{
+    arena::{Arena, NodeRef},
+    ast::{Expr, Ident, Operation, Pattern, Spanned, VarDef},
+    compile, compile_modules, compile_with_warnings, parse_block, parse_expr, parse_partial,
+    parse_type, tokenize,
+    visit::{fold_expr, walk_expr_mut, Fold, Visitor, VisitorMut},
+    FsLoader, MemoryLoader, ModuleLoader, TokenValue,
+};
+
+use std::collections::HashMap;
 
 const TEST_CODE: &str = include_str!("test.vy");
 
@@ -7,3 +16,1038 @@ fn it_works() {
     let result = compile(TEST_CODE).unwrap();
     dbg!(result);
 }
+
+#[test]
+fn arrays() {
+    let result = compile(
+        "let r = { 0, 1, 2, 3, 4}\nlet s = { x, }\nlet t = { x }\nlet u = { {1, 2}, {}, { a; b } }",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let r = {0.0, 1.0, 2.0, 3.0, 4.0}), (let s = {x,}), (let t = [x]), (let u = {{1.0, 2.0}, {}, [a, b]})]"
+    );
+    let result = compile("let r = {\n    1, 2,\n    3,\n}").unwrap();
+    assert_eq!(format!("{:?}", result), "[(let r = {1.0, 2.0, 3.0})]");
+}
+
+#[test]
+fn tuples_and_records() {
+    let result = compile(
+        "let t = (1, \"a\")\nlet u = ()\nlet v = (x,)\nlet w = (x)\nlet p = .{ x: 1, y: (a, b), z }\nlet q = f(a, .{})",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let t = (1.0, \"a\")), (let u = ()), (let v = (x,)), (let w = x), (let p = .{x: 1.0, y: (a, b), z}), (let q = f(a, .{}))]"
+    );
+    let result = compile("let add = (a, b) => a + b").unwrap();
+    assert_eq!(format!("{:?}", result), "[(let add = (a, b) => (a + b))]");
+}
+
+#[test]
+fn type_exprs() {
+    let result = compile(
+        "let f = (a: i32 | string, b: ?List<i32>, g: (i32, i32) => i32): (i32, {string}) => a\nx: ?(() | i32)\nlet h = (a): ((i32) => i32) => a",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let f = (a: i32 | string, b: ?List<i32>, g: (i32, i32) => i32): (i32, {string}) => a), x: ?(() | i32), (let h = (a): ((i32) => i32) => a)]"
+    );
+}
+
+#[test]
+fn type_decls() {
+    let result = compile(
+        "struct Point\n    x: f64\n    y: f64\nenum Option<T>\n    Some(T)\n    None\ntype Id = i32 | string\nlet p = Point.{ x: 1, y: 2 }\nlet s = Some(p)",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[struct Point { x: f64, y: f64 }, enum Option<T> { Some(T), None }, type Id = i32 | string, (let p = Point.{x: 1.0, y: 2.0}), (let s = Some(p))]"
+    );
+}
+
+#[test]
+fn patterns() {
+    let result = compile(
+        "let (a, _) = t\nlet .{ x, y: (b, c) } = p\nlet { first, ...rest } = xs\nlet f = ((a, b), .{ z }) => a",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let (a, _) = t), (let .{x, y: (b, c)} = p), (let {first, ...rest} = xs), (let f = ((a, b), .{z}) => a)]"
+    );
+    for invalid in &["1 = x", "a + b = c", "{ ...a, ...b } = c", "(...a) = b"] {
+        assert!(
+            compile(invalid).is_err(),
+            "{} is not a valid target",
+            invalid
+        );
+    }
+    let errors = compile("let x = 1\nf(a) = x").unwrap_err();
+    assert_eq!(errors[0].message, "Invalid assignment target `f(a)`.");
+}
+
+#[test]
+fn match_exprs() {
+    let src = "enum Shape\n    Circle(f64)\n    Rect(f64, f64)\n    Empty\n";
+    let result = compile(&format!(
+        "{}let a = match s\n    Circle(r) => r * r\n    Rect(w, h) if w == h =>\n        w * w\n    Rect(w, h) => w * h\n    Empty => 0\nlet b = match (x, y)\n    (1, \"a\") => 1\n    n: i32 => n\n    (_, _) => 0",
+        src
+    ))
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", &result.lines[1..]),
+        "[(let a = match s { Circle(r) => (r * r), Rect(w, h) if (w == h) => [(w * w)], Rect(w, h) => (w * h), Empty => 0.0 }), (let b = match (x, y) { (1.0, \"a\") => 1.0, n: i32 => n, (_, _) => 0.0 })]"
+    );
+    let errors = compile(&format!(
+        "{}let a = match s\n    Circle(_) => 1\n    Rect(w, h) if w == h => 2\n    Empty => 3",
+        src
+    ))
+    .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Non-exhaustive match, `Rect(_, _)` is not covered."
+    );
+    let errors = compile("let a = match n\n    1 => 1\n    2 => 2").unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Non-exhaustive match, `_` is not covered."
+    );
+    // A variable with a type tests the type of the value, which is not checked for exhaustiveness.
+    let result = compile("let a = match n\n    x: i32 => x + 1\n    s: string => 2").unwrap();
+    let Expr::Let(decl, _) = &result.lines[0] else {
+        panic!()
+    };
+    let Expr::Match(m) = &decl.value else {
+        panic!()
+    };
+    assert!(
+        matches!(&m.arms[0].pattern, Pattern::Var(VarDef { name, ty: Some(_) }) if name.name == "x")
+    );
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let a = match n { x: i32 => (x + 1.0), s: string => 2.0 })]"
+    );
+
+    let src = "enum Option<T>\n    Some(T)\n    None\nenum Reply\n    Yes\n    No\n    None\n";
+    compile(&format!(
+        "{}let a = match o\n    None => 0\n    Some(x) => x",
+        src
+    ))
+    .unwrap();
+    // A bare variant with a payload does not match every value.
+    let errors = compile(&format!(
+        "{}let a = match o\n    None => 0\n    Some => 1",
+        src
+    ))
+    .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Variant `Some` has a payload, match it by `Some(_)`."
+    );
+    assert_eq!(errors[0].range, 105..109);
+    let errors = compile(&format!(
+        "{}let a = match r\n    Yes => 1\n    None => 0",
+        src
+    ))
+    .unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "Non-exhaustive match, `No` is not covered."
+    );
+}
+
+#[test]
+fn jumps() {
+    let result = compile(
+        "let f = (n) =>\n    let i = 0\n    let r = i < n ^\n        i = i + 1\n        i == 2 ? continue\n        i > 5 ? break i\n        i\n    n < 0 ? return\n    r",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let f = (n) => [(let i = 0.0), (let r = ((i < n)^ [(i = (i + 1.0)), ((i == 2.0)? continue), ((i > 5.0)? break i), i])), ((n < 0.0)? return), r])]"
+    );
+    let errors = compile("x = 1\nbreak\ny = 1 ^ (() => continue)\nreturn x").unwrap_err();
+    let errors = errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.range.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            ("`break` outside of a loop.", 6..11),
+            ("`continue` outside of a loop.", 27..35),
+            ("`return` outside of a function.", 37..45),
+        ]
+    );
+}
+
+#[test]
+fn labeled_loops() {
+    let result = compile(
+        "'rows: y < h ^\n    let x = 0\n    'cols: x < w ^\n        x = x + 1\n        grid == x ? continue 'rows\n        x > y ? break 'rows x\nlet i = 0\nlet g = 'gen: i < 5 ^^\n    i = i + 1\n    i > 3 ? break 'gen",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "['rows: ((y < h)^ [(let x = 0.0), 'cols: ((x < w)^ [(x = (x + 1.0)), ((grid == x)? continue 'rows), ((x > y)? break 'rows x)])]), (let i = 0.0), (let g = 'gen: ((i < 5.0)^^ [(i = (i + 1.0)), ((i > 3.0)? break 'gen)]))]"
+    );
+    let errors =
+        compile("'a: x ^\n    'a: y ^\n        break 'b\n    f = () => continue 'a").unwrap_err();
+    let errors = errors
+        .iter()
+        .map(|e| e.message.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            "Label `'a` shadows the label of an enclosing loop.",
+            "Unknown label `'b`.",
+            "`continue` outside of a loop.",
+        ]
+    );
+    assert!(compile("'a: x = 1").is_err());
+}
+
+#[test]
+fn ranges_and_foreach() {
+    let result = compile(
+        "let r = 0..n + 1\nlet s = 10..=0 by 0 - 2\ni in 0..10 ^\n    i == 5 ? continue\n    print(i)\nlet squares = 'outer: (k, v) in pairs ^^ k * v",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let r = (0.0..(n + 1.0))), (let s = (10.0..=0.0 by (0.0 - 2.0))), (i in (0.0..10.0)^ [((i == 5.0)? continue), print(i)]), (let squares = 'outer: ((k, v) in pairs^^ (k * v)))]"
+    );
+    let errors = [
+        "0..1..2",
+        "0..1 by 2 by 3",
+        "x by 2",
+        "(x, 1) in xs ^ x",
+        "x in xs",
+    ]
+    .map(|src| compile(src).unwrap_err()[0].message.clone());
+    assert_eq!(
+        errors,
+        [
+            "Ranges could not be chained.",
+            "`by` must follow a range without a step.",
+            "`by` must follow a range without a step.",
+            "Invalid pattern `(x, 1.0)` before `in`.",
+            "`in` must be followed by a loop.",
+        ]
+    );
+}
+
+#[test]
+fn modules() {
+    let mut loader = MemoryLoader::new();
+    loader.insert(
+        "main",
+        "let geo = import \"geo\"\nlet .{sqrt} = import \"math\"\nsqrt(geo)",
+    );
+    loader.insert(
+        "geo",
+        "let m = import \"math\"\nexport let x = 4\nexport struct Point\n    x: f64",
+    );
+    loader.insert("math", "export const (sqrt, _) = ((x) => x, 0)");
+    let modules = compile_modules("main", &loader).unwrap();
+    let exports = modules
+        .iter()
+        .map(|m| {
+            let names = m.exports().iter().map(|i| format!("{:?}", i)).collect();
+            (m.path.as_str(), names)
+        })
+        .collect::<Vec<(_, Vec<_>)>>();
+    assert_eq!(
+        exports,
+        [
+            ("math", vec!["sqrt".to_string()]),
+            ("geo", vec!["x".to_string(), "Point".to_string()]),
+            ("main", vec![]),
+        ]
+    );
+
+    loader.insert("math", "let g = import \"geo\"\nexport const e = 2");
+    loader.insert("other", "let x = 1 ?\n    export let y = x");
+    loader.insert(
+        "main",
+        "import \"geo\"\nimport \"missing\"\nimport \"other\"",
+    );
+    let errors = compile_modules("main", &loader).unwrap_err();
+    let errors = errors
+        .iter()
+        .map(|e| {
+            (
+                e.message.as_str(),
+                e.module.as_deref().unwrap(),
+                e.range.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            ("Import cycle `geo` -> `math` -> `geo`.", "math", 8..20),
+            (
+                "Could not load module `missing`: no such module.",
+                "main",
+                13..29
+            ),
+            (
+                "`export` is only allowed at the top level of a module.",
+                "other",
+                16..32
+            ),
+        ]
+    );
+    assert_eq!(
+        compile("let x = 1\nexport x = 2").unwrap_err()[0].message,
+        "Only declarations could be exported."
+    );
+
+    loader.insert("math", "export const e = 2");
+    loader.insert(
+        "main",
+        "let .{ e, nope } = import \"./math\"\nlet m = import \"math\"\nimport \"/etc/passwd\"\nimport \"a/../../b\"",
+    );
+    let errors = compile_modules("./main", &loader).unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range.clone()))
+            .collect::<Vec<_>>(),
+        [
+            ("Module `math` does not export `nope`.", 10..14),
+            (
+                "Could not load module `/etc/passwd`: the path leads out of the root of modules.",
+                57..77
+            ),
+            (
+                "Could not load module `a/../../b`: the path leads out of the root of modules.",
+                78..96
+            ),
+        ]
+    );
+    let root = std::env::temp_dir();
+    assert_eq!(
+        FsLoader::new(&root).load("../etc/passwd"),
+        Err("the path leads out of the root of modules".to_string())
+    );
+    assert!(FsLoader::new(&root).load("/etc/passwd").is_err());
+}
+
+#[test]
+fn attributes() {
+    let (result, warnings) = compile_with_warnings(
+        "#[inline]\nlet square = (x) => x * x\n#[deprecated(\"use square\")]\n#[memo]\nexport const sq = square\nlet f = () =>\n    #[test]\n    square(2) == 4",
+    )
+    .unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[#[inline] (let square = (x) => (x * x)), #[deprecated(\"use square\")] #[memo] export (const sq = square), (let f = () => [#[test] (square(2.0) == 4.0)])]"
+    );
+    assert!(result.lines[0].attribute("inline").is_some());
+    assert!(result.lines[1].attribute("inline").is_none());
+    let warnings = warnings
+        .iter()
+        .map(|w| (w.message.as_str(), w.range.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(warnings, [("Unknown attribute `#[memo]`.", 64..71)]);
+    assert_eq!(
+        compile("x = 1\n#[inline]").unwrap_err()[0].message,
+        "Attributes must be followed by an expression."
+    );
+}
+
+#[test]
+fn long_operator_chains() {
+    let src = vec!["x"; 2000].join(" + ");
+    let result = compile(&src).unwrap();
+    let mut e = &result.lines[0];
+    let mut depth = 0;
+    while let Expr::Operation(o) = e {
+        match o.as_ref() {
+            Operation::Add(l, _) => e = l,
+            _ => unreachable!(),
+        }
+        depth += 1;
+    }
+    assert_eq!(depth, 1999);
+    assert_eq!(
+        format!(
+            "{:?}",
+            compile("let d = 0\na ? b !? c ? d = e !? f * g - h")
+                .unwrap()
+                .lines[1]
+        ),
+        "(a? b!? (c? (d = e)!? ((f * g) - h)))"
+    );
+}
+
+#[test]
+fn error_recovery() {
+    let (result, errors) =
+        parse_partial("a = 1 +\nb = )\n    c = 2\nf = () =>\n    x = (1, 2\n    x\n1 = y\nd = 4");
+    assert_eq!(
+        format!("{:?}", result),
+        "[<error>, <error>, (f = () => [<error>, x]), <error>, (d = 4.0)]"
+    );
+    let errors = errors
+        .iter()
+        .map(|e| (e.message.as_str(), e.range.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        [
+            ("The line ends unexpectedly after `+`.", 6..7),
+            ("Unexpected `)`.", 12..13),
+            ("The line ends unexpectedly after `2`.", 46..47),
+            ("Invalid assignment target `1.0`.", 54..55),
+        ]
+    );
+    match &result.lines[..2] {
+        [Expr::Error(_, _, a), Expr::Error(_, _, b)] => assert_eq!((a, b), (&(0..7), &(8..23))),
+        other => panic!("{:?}", other),
+    }
+    let (_, errors) = parse_partial("match");
+    assert_eq!(
+        errors[0].message,
+        "The line ends unexpectedly after `match`."
+    );
+    // Errors at the start of the input or in the lexer are described like the others.
+    let errors = ["\t\tx", "|> f", "x = \"abc", "x = \"a\\qb\"", "x = 1 $ 2"].map(|src| {
+        let e = compile(src).unwrap_err().remove(0);
+        (e.message, e.range)
+    });
+    assert_eq!(
+        errors,
+        [
+            ("Unexpected indented block.".to_string(), 0..0),
+            ("Unexpected `|>`.".to_string(), 0..2),
+            ("Unterminated string literal.".to_string(), 4..5),
+            ("Unknown escape sequence `\\q`.".to_string(), 6..7),
+            ("Unexpected `$`.".to_string(), 6..7),
+        ]
+    );
+}
+
+#[test]
+fn trailing_lines() {
+    for src in [
+        "let x = 1\n",
+        "let x = 1\n\n",
+        "let x = 1\n  \n",
+        "f = () =>\n    x\n    \n",
+    ] {
+        let (result, errors) = parse_partial(src);
+        assert_eq!((result.lines.len(), errors), (1, Vec::new()), "{:?}", src);
+    }
+    for src in ["", "\n", "// only comment", "// only comment\n"] {
+        let (result, errors) = parse_partial(src);
+        assert_eq!((result.lines.len(), errors), (0, Vec::new()), "{:?}", src);
+    }
+    assert!(compile("let x = 1\n").is_ok());
+}
+
+#[test]
+fn spans() {
+    let src = "let a = x > 1 ? f(x, 2) !? { y }\n'l: i < 10 ^\n    let g = (n: i32) => n * 2";
+    let result = compile(src).unwrap();
+    let spans = |e: &Expr| {
+        let mut spans = Vec::new();
+        let mut stack = vec![e];
+        while let Some(e) = stack.pop() {
+            spans.push(src[e.span()].to_string());
+            stack.extend(e.children().into_iter().rev());
+        }
+        spans
+    };
+    assert_eq!(
+        spans(&result.lines[0]),
+        [
+            "let a = x > 1 ? f(x, 2) !? { y }",
+            "x > 1 ? f(x, 2) !? { y }",
+            "x > 1",
+            "x",
+            "1",
+            "f(x, 2)",
+            "x",
+            "2",
+            "{ y }",
+            "y",
+        ]
+    );
+    assert_eq!(
+        spans(&result.lines[1]),
+        [
+            "'l: i < 10 ^\n    let g = (n: i32) => n * 2",
+            "i < 10",
+            "i",
+            "10",
+            "let g = (n: i32) => n * 2",
+            "let g = (n: i32) => n * 2",
+            "(n: i32) => n * 2",
+            "n * 2",
+            "n",
+            "2",
+        ]
+    );
+    assert_eq!(&src[result.span()], src);
+
+    let src = "let a = { b; c }\nlet d = {\n    e\n}";
+    let result = compile(src).unwrap();
+    let blocks = result
+        .lines
+        .iter()
+        .map(|e| match e {
+            Expr::Let(decl, _) => match &decl.value {
+                Expr::Block(block, _) => &src[block.span()],
+                e => panic!("{:?}", e),
+            },
+            e => panic!("{:?}", e),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(blocks, ["{ b; c }", "{\n    e\n}"]);
+    let (empty, _) = parse_partial("");
+    assert_eq!(empty.span(), 0..0);
+}
+
+#[test]
+fn deep_nesting() {
+    // Each level would double the parse time if brackets were parsed again on backtracking.
+    let n = 24;
+    let parens = format!("let x = {}1{}", "(".repeat(n), ")".repeat(n));
+    assert!(compile(&parens).is_ok());
+    let tuples = format!("let x = {}1{}", "(".repeat(n), ", 2)".repeat(n));
+    assert!(compile(&tuples).is_ok());
+    let funcs = format!("let x = {}1{}", "(a) => (".repeat(n), ")".repeat(n));
+    assert!(compile(&funcs).is_ok());
+    let blocks = format!("let x = {}1{}", "{ (".repeat(n), ") }".repeat(n));
+    assert!(compile(&blocks).is_ok());
+    let arrays = format!("let x = {}1{}", "{ (".repeat(n), "), }".repeat(n));
+    assert!(compile(&arrays).is_ok());
+}
+
+#[test]
+fn comparison_chains() {
+    let errors = [
+        "let y = x < 2 < 3",
+        "a == b >= c",
+        "a != (b == c) == d",
+        "f(a) >= b.c <= \"d\"",
+    ]
+    .map(|src| {
+        let e = &compile(src).unwrap_err()[0];
+        (e.message.clone(), e.range.clone())
+    });
+    assert_eq!(
+        errors,
+        [
+            (
+                "Comparison operators could not be chained, join the comparisons by `&&` such as `x < 2 && 2 < 3`.".to_string(),
+                14..15
+            ),
+            (
+                "Comparison operators could not be chained, add parentheses such as `a == (b >= c)`.".to_string(),
+                7..9
+            ),
+            (
+                "Comparison operators could not be chained, add parentheses such as `(a != (b == c)) == d`.".to_string(),
+                14..16
+            ),
+            (
+                "Comparison operators could not be chained, join the comparisons by `&&` such as `f(a) >= b.c && b.c <= \"d\"`.".to_string(),
+                12..14
+            ),
+        ]
+    );
+    assert!(compile("(a < b) == (c < d)").is_ok());
+    let (result, warnings) =
+        compile_with_warnings("a & b == c\nlet d = (e == f) | g\nlet h = e < f | g").unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(a & (b == c)), (let d = ((e == f) | g)), (let h = ((e < f) | g))]"
+    );
+    let warnings = warnings
+        .iter()
+        .map(|w| (w.message.as_str(), w.range.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        [
+            (
+                "`==` binds tighter than `&`, add parentheses to make the grouping clear.",
+                0..10
+            ),
+            (
+                "`<` binds tighter than `|`, add parentheses to make the grouping clear.",
+                40..49
+            ),
+        ]
+    );
+}
+
+#[test]
+fn fragments() {
+    let e = parse_expr("x > 0 ?\n    break x\n!? continue").unwrap();
+    assert_eq!(format!("{:?}", e), "((x > 0.0)? [break x]!? continue)");
+    assert!(matches!(e, Expr::Operation(o) if matches!(*o, Operation::IfElse(..))));
+    let t = parse_type("(i32, string) => ?{T} | ()").unwrap();
+    assert_eq!(format!("{:?}", t), "(i32, string) => ?{T} | ()");
+    let block = parse_block("a = 1\nreturn a").unwrap();
+    assert_eq!(format!("{:?}", block), "[(a = 1.0), return a]");
+    assert!(parse_expr("a = 1\nb = 2").is_err());
+    assert!(parse_type("i32 +").is_err());
+    assert!(parse_block("a = 1\nb = )").is_err());
+    let tokens = tokenize("f(x)").unwrap();
+    assert_eq!(tokens[1].0, TokenValue::Ident("f".to_string()));
+}
+
+#[test]
+fn doc_comments() {
+    let src = "// Not a doc comment.\n/// Adds two numbers.\n///\n/// Both must be finite.\nlet add = (\n    /// The left operand.\n    a: f64,\n    // Not documented.\n    b: f64,\n    /// The sum.\n): f64 => a + b // The body.\n\n  // Trailing comment.\nlet x = add(1, 2)";
+    let result = compile(src).unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[#[doc(\"Adds two numbers.\")] #[doc(\"\")] #[doc(\"Both must be finite.\")] (let add = (#[doc(\"The left operand.\")] a: f64, b: f64): f64 #[doc(\"The sum.\")] => (a + b)), (let x = add(1.0, 2.0))]"
+    );
+    assert_eq!(
+        result.lines[0].docs().as_deref(),
+        Some("Adds two numbers.\n\nBoth must be finite.")
+    );
+    assert_eq!(result.lines[1].docs(), None);
+}
+
+#[test]
+fn pipelines() {
+    let result = compile("let y = x |> f(1) |> g\nlet z = a + 1 |> h(b)").unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let y = g(f(x, 1.0))), (let z = h((a + 1.0), b))]"
+    );
+    let result = compile("let r = xs\n    |> map(double)\n\n    |> sum()\nr").unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let r = sum(map(xs, double))), r]"
+    );
+    assert_eq!(result.lines[0].span(), 0..43);
+    // A dedented `|>` continues the line at its indentation, after the blocks in it.
+    let result = compile("a ?\n    1\n!?\n    2\n|> f").unwrap();
+    assert_eq!(format!("{:?}", result), "[f((a? [1.0]!? [2.0]))]");
+    let result = compile("let g = () =>\n    x\n|> h\ng").unwrap();
+    assert_eq!(format!("{:?}", result), "[(let g = h(() => [x])), g]");
+    assert_eq!(result.lines[0].span(), 0..24);
+    let result =
+        compile("let f = () =>\n    let s = xs\n        |> sum\n    s\n    |> double").unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let f = () => [(let s = sum(xs)), double(s)])]"
+    );
+    let errors = compile("y = x |> 1").unwrap_err();
+    assert_eq!(
+        errors[0].message,
+        "`|>` must be followed by a function call."
+    );
+}
+
+#[test]
+fn call_args() {
+    let src = "let f = (x, scale: f64) => x * scale\nlet y = f(1, scale: 2)\nlet z = f(...args)\nlet w = g(a, ...b, c: 1)";
+    assert_eq!(
+        format!("{:?}", compile(src).unwrap()),
+        "[(let f = (x, scale: f64) => (x * scale)), (let y = f(1.0, scale: 2.0)), (let z = f(...args)), (let w = g(a, ...b, c: 1.0))]"
+    );
+    let messages = |src| {
+        compile(src)
+            .unwrap_err()
+            .into_iter()
+            .map(|d| d.message)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        messages(
+            "let f = (x, y) => x\nf(1, z: 2)\nf(1, x: 2, y: 3)\nf(y: 1)\nf(1, 2, 3)\nf(y: 1, 2)"
+        ),
+        [
+            "Unknown argument `z` of `f`.",
+            "Missing argument `y` of `f`.",
+            "Argument `x` of `f` is given more than once.",
+            "Missing argument `x` of `f`.",
+            "`f` takes 2 arguments but 3 are given.",
+            "Positional arguments must come before named arguments.",
+        ]
+    );
+    // Which function is called is not known if the name is assigned more than once.
+    assert!(compile("let f = (x) => x\nf = (y) => y\nf(z: 1)").is_ok());
+}
+
+#[test]
+fn unit_defaults() {
+    let src = "let n = c ? 1 ?? 0\nlet m = a ?? b ?? 0 + 1\nlet r = user?.name ?? \"anonymous\"\nlet s = p.pos.x\nlet t = shape?.area(2).value\nlet u = c ?.{x: 1}";
+    let result = compile(src).unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let n = (c? (1.0 ?? 0.0))), (let m = (a ?? (b ?? (0.0 + 1.0)))), (let r = (user?.name ?? \"anonymous\")), (let s = p.pos.x), (let t = shape?.area(2.0).value), (let u = (c? .{x: 1.0}))]"
+    );
+    assert_eq!(result.lines[4].span(), 93..121);
+
+    let (_, warnings) = compile_with_warnings(
+        "let a = (x + 1) ?? 0\nlet b = (c ? 1) ?? 0\nlet d = (c ? 1 !? \"b\") ?? 0\nlet e = (\"s\")?.len()\nlet f = g(x)?.len()",
+    )
+    .unwrap();
+    assert_eq!(
+        warnings
+            .iter()
+            .map(|w| (w.message.as_str(), w.range.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "The left operand of `??` is never `()`, so the right operand is never evaluated.",
+                8..15
+            ),
+            (
+                "The left operand of `??` is never `()`, so the right operand is never evaluated.",
+                50..64
+            ),
+            (
+                "`?.` on a value which is never `()`, use `.` instead.",
+                78..90
+            ),
+        ]
+    );
+}
+
+#[test]
+fn declarations() {
+    let src = "let count = 0\nconst limit = 10\nlet (a, b) = (1, 2)\ncount = count + a\nf = (n) =>\n    n = n + 1\n    count = n";
+    let errors = compile(src).unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range.clone(), e.related.clone()))
+            .collect::<Vec<_>>(),
+        [(
+            "Assignment to the undeclared variable `f`, declare it by `let` or `const` first.",
+            69..70,
+            vec![("No `f` is declared in this scope.".to_string(), 0..107)]
+        )]
+    );
+    let result = compile(&src.replace("\nf =", "\nconst f =")).unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let count = 0.0), (const limit = 10.0), (let (a, b) = (1.0, 2.0)), (count = (count + a)), (const f = (n) => [(n = (n + 1.0)), (count = n)])]"
+    );
+
+    let errors = compile("let count = 0\nconst limit = 10\ncuont = 1\nlimit = 5\nx in xs ^\n    let y = x\n    x = y\ny = 1").unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range.clone(), e.related.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "Assignment to the undeclared variable `cuont`, declare it by `let` or `const` first.",
+                31..36,
+                vec![
+                    ("No `cuont` is declared in this scope.".to_string(), 0..90),
+                    (
+                        "A variable of a similar name `count` is declared here.".to_string(),
+                        4..9
+                    )
+                ]
+            ),
+            (
+                "Assignment to the immutable variable `limit`.",
+                41..46,
+                vec![("`limit` is declared by `const` here.".to_string(), 20..25)]
+            ),
+            (
+                "Assignment to the undeclared variable `y`, declare it by `let` or `const` first.",
+                85..86,
+                vec![("No `y` is declared in this scope.".to_string(), 0..90)]
+            ),
+        ]
+    );
+    assert_eq!(
+        compile("let x + 1").unwrap_err()[0].message,
+        "`let` must be followed by an assignment."
+    );
+    let errors = compile("x > 0 ? let y = 1 !? let z = 2\ny = 3\nz = 3\nx ^\n    let w = x\nw = 3")
+        .unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "Assignment to the undeclared variable `y`, declare it by `let` or `const` first.",
+                31..32
+            ),
+            (
+                "Assignment to the undeclared variable `z`, declare it by `let` or `const` first.",
+                37..38
+            ),
+            (
+                "Assignment to the undeclared variable `w`, declare it by `let` or `const` first.",
+                61..62
+            ),
+        ]
+    );
+
+    let errors = |src| {
+        parse_partial(src)
+            .1
+            .into_iter()
+            .map(|e| (e.message, e.range))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(errors("let y = )"), [("Unexpected `)`.".to_string(), 8..9)]);
+    assert_eq!(
+        errors("let x = 1 +\nlet y = 2"),
+        [("The line ends unexpectedly after `+`.".to_string(), 10..11)]
+    );
+}
+
+#[test]
+fn throw_catch() {
+    let src = "let parse = (s: string): i32 | !ParseError =>\n    s == \"\" ? throw ParseError.{ at: 0 }\n    0\nlet n = parse(a) !! e: ParseError ? 0\nlet m = parse(a) + 1 !! _ ?\n    log(\"failed\")\n    throw Failed";
+    let result = compile(src).unwrap();
+    assert_eq!(
+        format!("{:?}", result),
+        "[(let parse = (s: string): i32 | !ParseError => [((s == \"\")? throw ParseError.{at: 0.0}), 0.0]), (let n = (parse(a) !! e: ParseError? 0.0)), (let m = ((parse(a) + 1.0) !! _? [log(\"failed\"), throw Failed]))]"
+    );
+    assert_eq!(result.lines[1].span(), 93..130);
+
+    let errors = compile("let f = (s): i32 =>\n    let n = g(s) !! e ? throw e\n    throw n\nlet h = (): i32 => x !! e ? 0").unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range.clone(), e.related.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "`throw` in a function returning `i32`, which does not include the type of thrown values such as `i32 | !E`.",
+                44..51,
+                vec![("The return type is annotated here.".to_string(), 13..16)]
+            ),
+            (
+                "`throw` in a function returning `i32`, which does not include the type of thrown values such as `i32 | !E`.",
+                56..63,
+                vec![("The return type is annotated here.".to_string(), 13..16)]
+            ),
+        ]
+    );
+    let errors = compile("enum NotFound\n    Missing(string)\nlet parse = (s: string): i32 | !ParseError => throw ParseError.{ at: 0 }\nlet find = (s: string): i32 | !NotFound => throw Missing(s)\nlet a = (s: string): i32 | !NotFound => parse(s) !! e: ParseError ? find(s)\nlet b = (s: string): i32 | !NotFound => parse(s)\nlet c = (s: string): i32 | !ParseError => throw Missing(s)\nlet d = (s: string): i32 => find(s) !! _ ? 0").unwrap_err();
+    assert_eq!(
+        errors
+            .iter()
+            .map(|e| (e.message.as_str(), e.range.clone(), e.related[0].1.clone()))
+            .collect::<Vec<_>>(),
+        [
+            (
+                "`parse` could throw `ParseError` in a function returning `i32 | !NotFound`, which does not include the type of thrown values such as `i32 | !NotFound | !ParseError`.",
+                283..291,
+                264..279
+            ),
+            (
+                "`throw` of `NotFound` in a function returning `i32 | !ParseError`, which does not include the type of thrown values such as `i32 | !ParseError | !NotFound`.",
+                334..350,
+                313..330
+            ),
+        ]
+    );
+    let errors =
+        ["x !! 0", "x !! 1 + 1 ? 0"].map(|src| compile(src).unwrap_err()[0].message.clone());
+    assert_eq!(
+        errors,
+        [
+            "`!!` must be followed by a pattern and a handler, such as `x !! e ? 0`.",
+            "Invalid pattern `(1.0 + 1.0)` after `!!`.",
+        ]
+    );
+}
+
+#[test]
+fn visitors() {
+    struct Names<'a>(Vec<&'a str>);
+    impl<'a> Visitor<'a> for Names<'a> {
+        fn visit_ident(&mut self, ident: &'a Ident) {
+            self.0.push(&ident.name);
+        }
+    }
+    let src = "let f = (x: T, .{ y }): ?U => match x\n    Some(z) if z > 0 => g(z, n: y) !! e ? 0\n    _ => 'outer: x.size < 1 ^\n        break 'outer";
+    let block = parse_block(src).unwrap();
+    let mut names = Names(Vec::new());
+    names.visit_block(&block);
+    assert_eq!(
+        names.0,
+        [
+            "f", "x", "T", "y", "U", "x", "Some", "z", "z", "g", "z", "n", "y", "e", "outer", "x",
+            "size", "outer"
+        ]
+    );
+
+    struct Rename;
+    impl VisitorMut for Rename {
+        fn visit_ident_mut(&mut self, ident: &mut Ident) {
+            if ident.name == "x" {
+                ident.name = "w".to_string();
+            }
+        }
+        fn visit_expr_mut(&mut self, e: &mut Expr) {
+            // Records are left unchanged.
+            if !matches!(e, Expr::Record(..)) {
+                walk_expr_mut(self, e);
+            }
+        }
+    }
+    let mut block = parse_block("let x = (x) => x + .{ x }.x").unwrap();
+    Rename.visit_block_mut(&mut block);
+    assert_eq!(format!("{:?}", block), "[(let w = (w) => (w + .{x}.w))]");
+
+    struct AddZero;
+    impl Fold for AddZero {
+        fn fold_expr(&mut self, e: Expr) -> Expr {
+            match fold_expr(self, e) {
+                Expr::Operation(o) => match *o {
+                    Operation::Add(l, Expr::LitNum(0.0, _)) => l,
+                    o => Expr::Operation(Box::new(o)),
+                },
+                e => e,
+            }
+        }
+    }
+    let e = AddZero.fold_expr(parse_expr("f(a + 0 + 0, { b + 0 }) * (c + 1)").unwrap());
+    assert_eq!(format!("{:?}", e), "(f(a, [b]) * (c + 1.0))");
+}
+
+#[test]
+fn arena() {
+    let block = compile("let f = (x) =>\n    x > 0 ? g(n: x)\nlet y = f(1)").unwrap();
+    let arena = Arena::new(&block);
+    assert_eq!(arena.len(), 29);
+    let (x, _) = arena
+        .iter()
+        .filter(|(_, n)| matches!(n.value, NodeRef::Ident(i) if i.name == "x"))
+        .last()
+        .unwrap();
+    assert_eq!(arena[x].span, 32..33);
+    let kinds = arena
+        .ancestors(x)
+        .map(|id| match arena[id].value {
+            NodeRef::Block(_) => "block",
+            NodeRef::Expr(_) => "expr",
+            NodeRef::Operation(_) => "operation",
+            NodeRef::Arg(_) => "arg",
+            _ => "other",
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        kinds,
+        [
+            "expr",
+            "arg",
+            "expr",
+            "operation",
+            "expr",
+            "block",
+            "expr",
+            "expr",
+            "expr",
+            "block"
+        ]
+    );
+    assert_eq!(arena.ancestors(x).last(), Some(arena.root()));
+
+    let decl = arena.id_of(NodeRef::Expr(&block.lines[1])).unwrap();
+    assert_eq!(arena[decl].parent, Some(arena.root()));
+    assert_eq!(arena[decl].span, 35..47);
+    let spans = arena[decl]
+        .children
+        .iter()
+        .map(|&id| arena[id].span.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(spans, [39..40, 43..47]);
+    let mut types = HashMap::new();
+    types.insert(arena[decl].children[1], "f64");
+    let Expr::Let(decl, _) = &block.lines[1] else {
+        unreachable!()
+    };
+    let call = arena.id_of(NodeRef::Expr(&decl.value)).unwrap();
+    assert_eq!(types.get(&call), Some(&"f64"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serialization() {
+    use crate::{
+        ast::{Block, TypeExpr},
+        Document, Token,
+    };
+
+    let tokens = Document::new(tokenize("let a = x ?? 0").unwrap());
+    let json = serde_json::to_string(&tokens).unwrap();
+    assert_eq!(
+        json,
+        r#"{"version":2,"content":[[{"Line":0},{"start":0,"end":0}],[{"Keyword":"let"},{"start":0,"end":3}],[{"Ident":"a"},{"start":4,"end":5}],[{"Punct":"="},{"start":6,"end":7}],[{"Ident":"x"},{"start":8,"end":9}],[{"Punct":"??"},{"start":10,"end":12}],[{"LitNum":0.0},{"start":13,"end":14}]]}"#
+    );
+    let parsed: Document<Vec<Token>> = serde_json::from_str(&json).unwrap();
+    assert_eq!(parsed.content, tokens.content);
+
+    let block = Document::new(compile("f(x, n: 1) ?? 0").unwrap());
+    let json = serde_json::to_string(&block).unwrap();
+    assert_eq!(
+        json,
+        r#"{"version":2,"content":{"lines":[{"Operation":{"Default":[{"Call":[{"name":"f","range":{"start":0,"end":1}},[{"Positional":{"Var":{"name":{"name":"x","range":{"start":2,"end":3}},"ty":null}}},{"Named":[{"name":"n","range":{"start":5,"end":6}},{"LitNum":[1.0,{"start":8,"end":9}]}]}],{"start":0,"end":10}]},{"LitNum":[0.0,{"start":14,"end":15}]}]}}],"range":{"start":0,"end":15}}}"#
+    );
+    let src = "let f = (s: string): i32 | !E =>\n    match s\n        \"\" => throw E.{ at: 0 }\n        _ => g(...s) |> h";
+    let block = compile(src).unwrap();
+    let json = serde_json::to_string(&Document::new(&block)).unwrap();
+    let parsed: Document<Block> = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", parsed.content), format!("{:?}", block));
+    assert_eq!(parsed.content.lines[0].span(), block.lines[0].span());
+
+    let errors = [
+        r#"{"version":1,"content":[]}"#,
+        r#"{"version":2,"content":[[{"Punct":"=<"},{"start":0,"end":2}]]}"#,
+    ]
+    .map(|json| {
+        serde_json::from_str::<Document<Vec<Token>>>(json)
+            .unwrap_err()
+            .to_string()
+    });
+    assert_eq!(
+        errors,
+        [
+            "unsupported format version 1, expected 2 at line 1 column 12",
+            "unknown punctuation `=<` at line 1 column 40",
+        ]
+    );
+
+    let union = parse_type("i32 | !E").unwrap();
+    let json = serde_json::to_string(&union).unwrap();
+    let parsed: TypeExpr = serde_json::from_str(&json).unwrap();
+    assert_eq!(format!("{:?}", parsed), format!("{:?}", union));
+    assert_eq!(parsed.span(), union.span());
+    let errors = [
+        r#"{"Union":[]}"#,
+        r#"{"Union":[{"Unit":{"start":0,"end":2}}]}"#,
+    ]
+    .map(|json| {
+        serde_json::from_str::<TypeExpr>(json)
+            .unwrap_err()
+            .to_string()
+    });
+    assert_eq!(
+        errors,
+        [
+            "invalid length 0, expected at least two members of a union",
+            "invalid length 1, expected at least two members of a union",
+        ]
+    );
+}
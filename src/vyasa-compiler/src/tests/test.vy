@@ -1,10 +1,10 @@
-max_2_pow = (num) =>
-    result = 1
+let max_2_pow = (num) =>
+    let result = 1
     result < num ^
         result = result * 2
     result
 
-a = 3 > 2 ?
+let a = 3 > 2 ?
     5
 !? 3 > 1 ?
     6
@@ -0,0 +1,1118 @@
+//! Traversals over the AST, where each method of a trait handles one kind of node, and by default
+//! walks into the nodes inside it by the function for the same kind of node: `walk_*` for
+//! `Visitor`, `walk_*_mut` for `VisitorMut` and `fold_*` for `Fold`. An overriding method should
+//! call that function to keep walking into the nodes inside.
+
+use crate::ast::{
+    Arg, Arm, Attribute, Block, EnumDecl, Expr, Field, FieldDef, FieldPattern, Func, Ident, Let,
+    Match, Member, Operation, Param, Pattern, StructDecl, TypeAlias, TypeExpr, VarDef, Variant,
+};
+
+/// Visits the nodes of the AST by shared references, which could be kept for the lifetime `'a`.
+pub trait Visitor<'a> {
+    fn visit_block(&mut self, block: &'a Block) {
+        walk_block(self, block)
+    }
+
+    fn visit_expr(&mut self, e: &'a Expr) {
+        walk_expr(self, e)
+    }
+
+    fn visit_operation(&mut self, o: &'a Operation) {
+        walk_operation(self, o)
+    }
+
+    fn visit_pattern(&mut self, p: &'a Pattern) {
+        walk_pattern(self, p)
+    }
+
+    fn visit_type_expr(&mut self, t: &'a TypeExpr) {
+        walk_type_expr(self, t)
+    }
+
+    fn visit_func(&mut self, func: &'a Func) {
+        walk_func(self, func)
+    }
+
+    fn visit_arg(&mut self, arg: &'a Arg) {
+        walk_arg(self, arg)
+    }
+
+    fn visit_attribute(&mut self, attribute: &'a Attribute) {
+        walk_attribute(self, attribute)
+    }
+
+    fn visit_arm(&mut self, arm: &'a Arm) {
+        walk_arm(self, arm)
+    }
+
+    fn visit_ident(&mut self, _ident: &'a Ident) {}
+}
+
+pub fn walk_block<'a, V: Visitor<'a> + ?Sized>(v: &mut V, Block { lines, .. }: &'a Block) {
+    for e in lines {
+        v.visit_expr(e);
+    }
+}
+
+pub fn walk_expr<'a, V: Visitor<'a> + ?Sized>(v: &mut V, e: &'a Expr) {
+    match e {
+        Expr::LitNum(..) | Expr::LitStr(..) | Expr::LastLine(_) | Expr::Import(..) => {}
+        Expr::Error(..) => {}
+        Expr::Var(var) => walk_var_def(v, var),
+        Expr::Block(block, _) => v.visit_block(block),
+        Expr::Array(items, _) | Expr::Tuple(items, _) => {
+            for item in items {
+                v.visit_expr(item);
+            }
+        }
+        Expr::Record(name, fields, _) => {
+            if let Some(name) = name {
+                v.visit_ident(name);
+            }
+            for Field { name, value } in fields {
+                v.visit_ident(name);
+                if let Some(value) = value {
+                    v.visit_expr(value);
+                }
+            }
+        }
+        Expr::Paren(e, _) | Expr::Spread(e, _) | Expr::Throw(e, _) | Expr::Export(e, _) => {
+            v.visit_expr(e)
+        }
+        Expr::Operation(o) => v.visit_operation(o),
+        Expr::Call(name, args, _) => {
+            v.visit_ident(name);
+            for arg in args {
+                v.visit_arg(arg);
+            }
+        }
+        Expr::Member(member, _) => {
+            let Member {
+                receiver,
+                name,
+                args,
+                ..
+            } = member.as_ref();
+            v.visit_expr(receiver);
+            v.visit_ident(name);
+            for arg in args.iter().flatten() {
+                v.visit_arg(arg);
+            }
+        }
+        Expr::Func(func, _) => v.visit_func(func),
+        Expr::Struct(decl) => {
+            v.visit_ident(&decl.name);
+            for param in &decl.type_params {
+                v.visit_ident(param);
+            }
+            for field in &decl.fields {
+                v.visit_ident(&field.name);
+                v.visit_type_expr(&field.ty);
+            }
+        }
+        Expr::Enum(decl) => {
+            v.visit_ident(&decl.name);
+            for param in &decl.type_params {
+                v.visit_ident(param);
+            }
+            for variant in &decl.variants {
+                v.visit_ident(&variant.name);
+                for t in &variant.payload {
+                    v.visit_type_expr(t);
+                }
+            }
+        }
+        Expr::TypeAlias(alias) => {
+            v.visit_ident(&alias.name);
+            for param in &alias.type_params {
+                v.visit_ident(param);
+            }
+            v.visit_type_expr(&alias.ty);
+        }
+        Expr::Match(m) => {
+            let Match { value, arms, .. } = m.as_ref();
+            v.visit_expr(value);
+            for arm in arms {
+                v.visit_arm(arm);
+            }
+        }
+        Expr::Break(label, value, _) => {
+            if let Some(label) = label {
+                v.visit_ident(label);
+            }
+            if let Some(value) = value {
+                v.visit_expr(value);
+            }
+        }
+        Expr::Continue(label, _) => {
+            if let Some(label) = label {
+                v.visit_ident(label);
+            }
+        }
+        Expr::Return(value, _) => {
+            if let Some(value) = value {
+                v.visit_expr(value);
+            }
+        }
+        Expr::Let(decl, _) => {
+            let Let { pattern, value, .. } = decl.as_ref();
+            v.visit_pattern(pattern);
+            v.visit_expr(value);
+        }
+        Expr::Attributed(attributes, e) => {
+            for attribute in attributes {
+                v.visit_attribute(attribute);
+            }
+            v.visit_expr(e);
+        }
+    }
+}
+
+pub fn walk_operation<'a, V: Visitor<'a> + ?Sized>(v: &mut V, o: &'a Operation) {
+    match o {
+        Operation::IfElse(c, b, e) => {
+            v.visit_expr(c);
+            v.visit_expr(b);
+            v.visit_expr(e);
+        }
+        Operation::While(c, body, label) | Operation::Generator(c, body, label) => {
+            if let Some(label) = label {
+                v.visit_ident(label);
+            }
+            v.visit_expr(c);
+            v.visit_expr(body);
+        }
+        Operation::ForEach(p, iterable, body, label)
+        | Operation::ForEachGenerator(p, iterable, body, label) => {
+            if let Some(label) = label {
+                v.visit_ident(label);
+            }
+            v.visit_pattern(p);
+            v.visit_expr(iterable);
+            v.visit_expr(body);
+        }
+        Operation::Assign(p, value) => {
+            v.visit_pattern(p);
+            v.visit_expr(value);
+        }
+        Operation::Catch(body, p, handler) => {
+            v.visit_expr(body);
+            v.visit_pattern(p);
+            v.visit_expr(handler);
+        }
+        Operation::Range(l, r, step) | Operation::RangeInclusive(l, r, step) => {
+            v.visit_expr(l);
+            v.visit_expr(r);
+            if let Some(step) = step {
+                v.visit_expr(step);
+            }
+        }
+        Operation::If(l, r)
+        | Operation::Add(l, r)
+        | Operation::Sub(l, r)
+        | Operation::Mul(l, r)
+        | Operation::Div(l, r)
+        | Operation::BitAnd(l, r)
+        | Operation::BitOr(l, r)
+        | Operation::EQ(l, r)
+        | Operation::NE(l, r)
+        | Operation::GT(l, r)
+        | Operation::LT(l, r)
+        | Operation::GE(l, r)
+        | Operation::LE(l, r)
+        | Operation::And(l, r)
+        | Operation::Or(l, r)
+        | Operation::Default(l, r) => {
+            v.visit_expr(l);
+            v.visit_expr(r);
+        }
+    }
+}
+
+pub fn walk_pattern<'a, V: Visitor<'a> + ?Sized>(v: &mut V, p: &'a Pattern) {
+    match p {
+        Pattern::Var(var) => walk_var_def(v, var),
+        Pattern::Wildcard(_) | Pattern::LitNum(..) | Pattern::LitStr(..) => {}
+        Pattern::Tuple(items, _) | Pattern::Array(items, _) => {
+            for item in items {
+                v.visit_pattern(item);
+            }
+        }
+        Pattern::Record(name, fields, _) => {
+            if let Some(name) = name {
+                v.visit_ident(name);
+            }
+            for FieldPattern { name, pattern } in fields {
+                v.visit_ident(name);
+                if let Some(p) = pattern {
+                    v.visit_pattern(p);
+                }
+            }
+        }
+        Pattern::Rest(name, _) => {
+            if let Some(name) = name {
+                v.visit_ident(name);
+            }
+        }
+        Pattern::Variant(name, items, _) => {
+            v.visit_ident(name);
+            for item in items {
+                v.visit_pattern(item);
+            }
+        }
+    }
+}
+
+pub fn walk_type_expr<'a, V: Visitor<'a> + ?Sized>(v: &mut V, t: &'a TypeExpr) {
+    match t {
+        TypeExpr::Name(name) => v.visit_ident(name),
+        TypeExpr::Unit(_) => {}
+        TypeExpr::Tuple(items, _) | TypeExpr::Union(items) => {
+            for item in items {
+                v.visit_type_expr(item);
+            }
+        }
+        TypeExpr::Array(t, _) | TypeExpr::Optional(t, _) | TypeExpr::Thrown(t, _) => {
+            v.visit_type_expr(t)
+        }
+        TypeExpr::Func(func, _) => {
+            let (params, ret) = func.as_ref();
+            for param in params {
+                v.visit_type_expr(param);
+            }
+            v.visit_type_expr(ret);
+        }
+        TypeExpr::Generic(name, args, _) => {
+            v.visit_ident(name);
+            for arg in args {
+                v.visit_type_expr(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_func<'a, V: Visitor<'a> + ?Sized>(
+    v: &mut V,
+    Func {
+        params,
+        ret_type,
+        body,
+        ..
+    }: &'a Func,
+) {
+    for Param { pattern, .. } in params {
+        v.visit_pattern(pattern);
+    }
+    if let Some(ret) = ret_type {
+        v.visit_type_expr(ret);
+    }
+    v.visit_expr(body);
+}
+
+pub fn walk_arg<'a, V: Visitor<'a> + ?Sized>(v: &mut V, arg: &'a Arg) {
+    match arg {
+        Arg::Positional(e) | Arg::Spread(e, _) => v.visit_expr(e),
+        Arg::Named(name, e) => {
+            v.visit_ident(name);
+            v.visit_expr(e);
+        }
+    }
+}
+
+pub fn walk_attribute<'a, V: Visitor<'a> + ?Sized>(
+    v: &mut V,
+    Attribute { name, args, .. }: &'a Attribute,
+) {
+    v.visit_ident(name);
+    for arg in args {
+        v.visit_expr(arg);
+    }
+}
+
+pub fn walk_arm<'a, V: Visitor<'a> + ?Sized>(
+    v: &mut V,
+    Arm {
+        pattern,
+        guard,
+        body,
+    }: &'a Arm,
+) {
+    v.visit_pattern(pattern);
+    if let Some(guard) = guard {
+        v.visit_expr(guard);
+    }
+    v.visit_expr(body);
+}
+
+fn walk_var_def<'a, V: Visitor<'a> + ?Sized>(v: &mut V, VarDef { name, ty }: &'a VarDef) {
+    v.visit_ident(name);
+    if let Some(t) = ty {
+        v.visit_type_expr(t);
+    }
+}
+
+/// Visits the nodes of the AST by mutable references, to change them in place.
+pub trait VisitorMut {
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block)
+    }
+
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        walk_expr_mut(self, e)
+    }
+
+    fn visit_operation_mut(&mut self, o: &mut Operation) {
+        walk_operation_mut(self, o)
+    }
+
+    fn visit_pattern_mut(&mut self, p: &mut Pattern) {
+        walk_pattern_mut(self, p)
+    }
+
+    fn visit_type_expr_mut(&mut self, t: &mut TypeExpr) {
+        walk_type_expr_mut(self, t)
+    }
+
+    fn visit_func_mut(&mut self, func: &mut Func) {
+        walk_func_mut(self, func)
+    }
+
+    fn visit_arg_mut(&mut self, arg: &mut Arg) {
+        walk_arg_mut(self, arg)
+    }
+
+    fn visit_attribute_mut(&mut self, attribute: &mut Attribute) {
+        walk_attribute_mut(self, attribute)
+    }
+
+    fn visit_arm_mut(&mut self, arm: &mut Arm) {
+        walk_arm_mut(self, arm)
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Ident) {}
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, Block { lines, .. }: &mut Block) {
+    for e in lines {
+        v.visit_expr_mut(e);
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, e: &mut Expr) {
+    match e {
+        Expr::LitNum(..) | Expr::LitStr(..) | Expr::LastLine(_) | Expr::Import(..) => {}
+        Expr::Error(..) => {}
+        Expr::Var(var) => walk_var_def_mut(v, var),
+        Expr::Block(block, _) => v.visit_block_mut(block),
+        Expr::Array(items, _) | Expr::Tuple(items, _) => {
+            for item in items {
+                v.visit_expr_mut(item);
+            }
+        }
+        Expr::Record(name, fields, _) => {
+            if let Some(name) = name {
+                v.visit_ident_mut(name);
+            }
+            for Field { name, value } in fields {
+                v.visit_ident_mut(name);
+                if let Some(value) = value {
+                    v.visit_expr_mut(value);
+                }
+            }
+        }
+        Expr::Paren(e, _) | Expr::Spread(e, _) | Expr::Throw(e, _) | Expr::Export(e, _) => {
+            v.visit_expr_mut(e)
+        }
+        Expr::Operation(o) => v.visit_operation_mut(o),
+        Expr::Call(name, args, _) => {
+            v.visit_ident_mut(name);
+            for arg in args {
+                v.visit_arg_mut(arg);
+            }
+        }
+        Expr::Member(member, _) => {
+            let Member {
+                receiver,
+                name,
+                args,
+                ..
+            } = member.as_mut();
+            v.visit_expr_mut(receiver);
+            v.visit_ident_mut(name);
+            for arg in args.iter_mut().flatten() {
+                v.visit_arg_mut(arg);
+            }
+        }
+        Expr::Func(func, _) => v.visit_func_mut(func),
+        Expr::Struct(decl) => {
+            v.visit_ident_mut(&mut decl.name);
+            for param in &mut decl.type_params {
+                v.visit_ident_mut(param);
+            }
+            for field in &mut decl.fields {
+                v.visit_ident_mut(&mut field.name);
+                v.visit_type_expr_mut(&mut field.ty);
+            }
+        }
+        Expr::Enum(decl) => {
+            v.visit_ident_mut(&mut decl.name);
+            for param in &mut decl.type_params {
+                v.visit_ident_mut(param);
+            }
+            for variant in &mut decl.variants {
+                v.visit_ident_mut(&mut variant.name);
+                for t in &mut variant.payload {
+                    v.visit_type_expr_mut(t);
+                }
+            }
+        }
+        Expr::TypeAlias(alias) => {
+            v.visit_ident_mut(&mut alias.name);
+            for param in &mut alias.type_params {
+                v.visit_ident_mut(param);
+            }
+            v.visit_type_expr_mut(&mut alias.ty);
+        }
+        Expr::Match(m) => {
+            let Match { value, arms, .. } = m.as_mut();
+            v.visit_expr_mut(value);
+            for arm in arms {
+                v.visit_arm_mut(arm);
+            }
+        }
+        Expr::Break(label, value, _) => {
+            if let Some(label) = label {
+                v.visit_ident_mut(label);
+            }
+            if let Some(value) = value {
+                v.visit_expr_mut(value);
+            }
+        }
+        Expr::Continue(label, _) => {
+            if let Some(label) = label {
+                v.visit_ident_mut(label);
+            }
+        }
+        Expr::Return(value, _) => {
+            if let Some(value) = value {
+                v.visit_expr_mut(value);
+            }
+        }
+        Expr::Let(decl, _) => {
+            let Let { pattern, value, .. } = decl.as_mut();
+            v.visit_pattern_mut(pattern);
+            v.visit_expr_mut(value);
+        }
+        Expr::Attributed(attributes, e) => {
+            for attribute in attributes {
+                v.visit_attribute_mut(attribute);
+            }
+            v.visit_expr_mut(e);
+        }
+    }
+}
+
+pub fn walk_operation_mut<V: VisitorMut + ?Sized>(v: &mut V, o: &mut Operation) {
+    match o {
+        Operation::IfElse(c, b, e) => {
+            v.visit_expr_mut(c);
+            v.visit_expr_mut(b);
+            v.visit_expr_mut(e);
+        }
+        Operation::While(c, body, label) | Operation::Generator(c, body, label) => {
+            if let Some(label) = label {
+                v.visit_ident_mut(label);
+            }
+            v.visit_expr_mut(c);
+            v.visit_expr_mut(body);
+        }
+        Operation::ForEach(p, iterable, body, label)
+        | Operation::ForEachGenerator(p, iterable, body, label) => {
+            if let Some(label) = label {
+                v.visit_ident_mut(label);
+            }
+            v.visit_pattern_mut(p);
+            v.visit_expr_mut(iterable);
+            v.visit_expr_mut(body);
+        }
+        Operation::Assign(p, value) => {
+            v.visit_pattern_mut(p);
+            v.visit_expr_mut(value);
+        }
+        Operation::Catch(body, p, handler) => {
+            v.visit_expr_mut(body);
+            v.visit_pattern_mut(p);
+            v.visit_expr_mut(handler);
+        }
+        Operation::Range(l, r, step) | Operation::RangeInclusive(l, r, step) => {
+            v.visit_expr_mut(l);
+            v.visit_expr_mut(r);
+            if let Some(step) = step {
+                v.visit_expr_mut(step);
+            }
+        }
+        Operation::If(l, r)
+        | Operation::Add(l, r)
+        | Operation::Sub(l, r)
+        | Operation::Mul(l, r)
+        | Operation::Div(l, r)
+        | Operation::BitAnd(l, r)
+        | Operation::BitOr(l, r)
+        | Operation::EQ(l, r)
+        | Operation::NE(l, r)
+        | Operation::GT(l, r)
+        | Operation::LT(l, r)
+        | Operation::GE(l, r)
+        | Operation::LE(l, r)
+        | Operation::And(l, r)
+        | Operation::Or(l, r)
+        | Operation::Default(l, r) => {
+            v.visit_expr_mut(l);
+            v.visit_expr_mut(r);
+        }
+    }
+}
+
+pub fn walk_pattern_mut<V: VisitorMut + ?Sized>(v: &mut V, p: &mut Pattern) {
+    match p {
+        Pattern::Var(var) => walk_var_def_mut(v, var),
+        Pattern::Wildcard(_) | Pattern::LitNum(..) | Pattern::LitStr(..) => {}
+        Pattern::Tuple(items, _) | Pattern::Array(items, _) => {
+            for item in items {
+                v.visit_pattern_mut(item);
+            }
+        }
+        Pattern::Record(name, fields, _) => {
+            if let Some(name) = name {
+                v.visit_ident_mut(name);
+            }
+            for FieldPattern { name, pattern } in fields {
+                v.visit_ident_mut(name);
+                if let Some(p) = pattern {
+                    v.visit_pattern_mut(p);
+                }
+            }
+        }
+        Pattern::Rest(name, _) => {
+            if let Some(name) = name {
+                v.visit_ident_mut(name);
+            }
+        }
+        Pattern::Variant(name, items, _) => {
+            v.visit_ident_mut(name);
+            for item in items {
+                v.visit_pattern_mut(item);
+            }
+        }
+    }
+}
+
+pub fn walk_type_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, t: &mut TypeExpr) {
+    match t {
+        TypeExpr::Name(name) => v.visit_ident_mut(name),
+        TypeExpr::Unit(_) => {}
+        TypeExpr::Tuple(items, _) | TypeExpr::Union(items) => {
+            for item in items {
+                v.visit_type_expr_mut(item);
+            }
+        }
+        TypeExpr::Array(t, _) | TypeExpr::Optional(t, _) | TypeExpr::Thrown(t, _) => {
+            v.visit_type_expr_mut(t)
+        }
+        TypeExpr::Func(func, _) => {
+            let (params, ret) = func.as_mut();
+            for param in params {
+                v.visit_type_expr_mut(param);
+            }
+            v.visit_type_expr_mut(ret);
+        }
+        TypeExpr::Generic(name, args, _) => {
+            v.visit_ident_mut(name);
+            for arg in args {
+                v.visit_type_expr_mut(arg);
+            }
+        }
+    }
+}
+
+pub fn walk_func_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    Func {
+        params,
+        ret_type,
+        body,
+        ..
+    }: &mut Func,
+) {
+    for Param { pattern, .. } in params {
+        v.visit_pattern_mut(pattern);
+    }
+    if let Some(ret) = ret_type {
+        v.visit_type_expr_mut(ret);
+    }
+    v.visit_expr_mut(body);
+}
+
+pub fn walk_arg_mut<V: VisitorMut + ?Sized>(v: &mut V, arg: &mut Arg) {
+    match arg {
+        Arg::Positional(e) | Arg::Spread(e, _) => v.visit_expr_mut(e),
+        Arg::Named(name, e) => {
+            v.visit_ident_mut(name);
+            v.visit_expr_mut(e);
+        }
+    }
+}
+
+pub fn walk_attribute_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    Attribute { name, args, .. }: &mut Attribute,
+) {
+    v.visit_ident_mut(name);
+    for arg in args {
+        v.visit_expr_mut(arg);
+    }
+}
+
+pub fn walk_arm_mut<V: VisitorMut + ?Sized>(
+    v: &mut V,
+    Arm {
+        pattern,
+        guard,
+        body,
+    }: &mut Arm,
+) {
+    v.visit_pattern_mut(pattern);
+    if let Some(guard) = guard {
+        v.visit_expr_mut(guard);
+    }
+    v.visit_expr_mut(body);
+}
+
+fn walk_var_def_mut<V: VisitorMut + ?Sized>(v: &mut V, VarDef { name, ty }: &mut VarDef) {
+    v.visit_ident_mut(name);
+    if let Some(t) = ty {
+        v.visit_type_expr_mut(t);
+    }
+}
+
+/// Rebuilds the AST from the nodes given by value, where each method gives the node in place of
+/// the one given.
+pub trait Fold {
+    fn fold_block(&mut self, block: Block) -> Block {
+        fold_block(self, block)
+    }
+
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        fold_expr(self, e)
+    }
+
+    fn fold_operation(&mut self, o: Operation) -> Operation {
+        fold_operation(self, o)
+    }
+
+    fn fold_pattern(&mut self, p: Pattern) -> Pattern {
+        fold_pattern(self, p)
+    }
+
+    fn fold_type_expr(&mut self, t: TypeExpr) -> TypeExpr {
+        fold_type_expr(self, t)
+    }
+
+    fn fold_func(&mut self, func: Func) -> Func {
+        fold_func(self, func)
+    }
+
+    fn fold_arg(&mut self, arg: Arg) -> Arg {
+        fold_arg(self, arg)
+    }
+
+    fn fold_attribute(&mut self, attribute: Attribute) -> Attribute {
+        fold_attribute(self, attribute)
+    }
+
+    fn fold_arm(&mut self, arm: Arm) -> Arm {
+        fold_arm(self, arm)
+    }
+
+    fn fold_ident(&mut self, ident: Ident) -> Ident {
+        ident
+    }
+}
+
+pub fn fold_block<F: Fold + ?Sized>(f: &mut F, mut block: Block) -> Block {
+    block.lines = fold_exprs(f, block.lines);
+    block
+}
+
+pub fn fold_expr<F: Fold + ?Sized>(f: &mut F, e: Expr) -> Expr {
+    match e {
+        e @ (Expr::LitNum(..)
+        | Expr::LitStr(..)
+        | Expr::LastLine(_)
+        | Expr::Import(..)
+        | Expr::Error(..)) => e,
+        Expr::Var(var) => Expr::Var(fold_var_def(f, var)),
+        Expr::Block(block, range) => Expr::Block(f.fold_block(block), range),
+        Expr::Array(items, range) => Expr::Array(fold_exprs(f, items), range),
+        Expr::Tuple(items, range) => Expr::Tuple(fold_exprs(f, items), range),
+        Expr::Record(name, fields, range) => {
+            let name = name.map(|name| f.fold_ident(name));
+            let fields = fields
+                .into_iter()
+                .map(|Field { name, value }| Field {
+                    name: f.fold_ident(name),
+                    value: value.map(|e| f.fold_expr(e)),
+                })
+                .collect();
+            Expr::Record(name, fields, range)
+        }
+        Expr::Paren(e, range) => Expr::Paren(fold_box(f, e), range),
+        Expr::Spread(e, range) => Expr::Spread(fold_box(f, e), range),
+        Expr::Operation(o) => Expr::Operation(Box::new(f.fold_operation(*o))),
+        Expr::Call(name, args, range) => {
+            let name = f.fold_ident(name);
+            Expr::Call(
+                name,
+                args.into_iter().map(|a| f.fold_arg(a)).collect(),
+                range,
+            )
+        }
+        Expr::Member(member, range) => {
+            let Member {
+                receiver,
+                name,
+                args,
+                safe,
+            } = *member;
+            let receiver = f.fold_expr(receiver);
+            let name = f.fold_ident(name);
+            let args = args.map(|args| args.into_iter().map(|a| f.fold_arg(a)).collect());
+            let member = Member {
+                receiver,
+                name,
+                args,
+                safe,
+            };
+            Expr::Member(Box::new(member), range)
+        }
+        Expr::Func(func, range) => Expr::Func(Box::new(f.fold_func(*func)), range),
+        Expr::Struct(decl) => {
+            let StructDecl {
+                name,
+                type_params,
+                fields,
+                range,
+            } = *decl;
+            let name = f.fold_ident(name);
+            let type_params = fold_idents(f, type_params);
+            let fields = fields
+                .into_iter()
+                .map(|FieldDef { name, ty }| FieldDef {
+                    name: f.fold_ident(name),
+                    ty: f.fold_type_expr(ty),
+                })
+                .collect();
+            let decl = StructDecl {
+                name,
+                type_params,
+                fields,
+                range,
+            };
+            Expr::Struct(Box::new(decl))
+        }
+        Expr::Enum(decl) => {
+            let EnumDecl {
+                name,
+                type_params,
+                variants,
+                range,
+            } = *decl;
+            let name = f.fold_ident(name);
+            let type_params = fold_idents(f, type_params);
+            let variants = variants
+                .into_iter()
+                .map(
+                    |Variant {
+                         name,
+                         payload,
+                         range,
+                     }| Variant {
+                        name: f.fold_ident(name),
+                        payload: fold_type_exprs(f, payload),
+                        range,
+                    },
+                )
+                .collect();
+            let decl = EnumDecl {
+                name,
+                type_params,
+                variants,
+                range,
+            };
+            Expr::Enum(Box::new(decl))
+        }
+        Expr::TypeAlias(alias) => {
+            let TypeAlias {
+                name,
+                type_params,
+                ty,
+                range,
+            } = *alias;
+            let name = f.fold_ident(name);
+            let type_params = fold_idents(f, type_params);
+            let ty = f.fold_type_expr(ty);
+            let alias = TypeAlias {
+                name,
+                type_params,
+                ty,
+                range,
+            };
+            Expr::TypeAlias(Box::new(alias))
+        }
+        Expr::Match(m) => {
+            let Match { value, arms, range } = *m;
+            let value = f.fold_expr(value);
+            let arms = arms.into_iter().map(|arm| f.fold_arm(arm)).collect();
+            Expr::Match(Box::new(Match { value, arms, range }))
+        }
+        Expr::Break(label, value, range) => {
+            let label = label.map(|label| f.fold_ident(label));
+            Expr::Break(label, value.map(|e| fold_box(f, e)), range)
+        }
+        Expr::Continue(label, range) => Expr::Continue(label.map(|l| f.fold_ident(l)), range),
+        Expr::Return(value, range) => Expr::Return(value.map(|e| fold_box(f, e)), range),
+        Expr::Throw(e, range) => Expr::Throw(fold_box(f, e), range),
+        Expr::Let(decl, range) => {
+            let Let {
+                pattern,
+                value,
+                constant,
+            } = *decl;
+            let pattern = f.fold_pattern(pattern);
+            let value = f.fold_expr(value);
+            let decl = Let {
+                pattern,
+                value,
+                constant,
+            };
+            Expr::Let(Box::new(decl), range)
+        }
+        Expr::Export(e, range) => Expr::Export(fold_box(f, e), range),
+        Expr::Attributed(attributes, e) => {
+            let attributes = attributes
+                .into_iter()
+                .map(|a| f.fold_attribute(a))
+                .collect();
+            Expr::Attributed(attributes, fold_box(f, e))
+        }
+    }
+}
+
+pub fn fold_operation<F: Fold + ?Sized>(f: &mut F, o: Operation) -> Operation {
+    use Operation::*;
+    let mut binary = |l, r, op: fn(Expr, Expr) -> Operation| {
+        let l = f.fold_expr(l);
+        op(l, f.fold_expr(r))
+    };
+    match o {
+        If(l, r) => binary(l, r, If),
+        Add(l, r) => binary(l, r, Add),
+        Sub(l, r) => binary(l, r, Sub),
+        Mul(l, r) => binary(l, r, Mul),
+        Div(l, r) => binary(l, r, Div),
+        BitAnd(l, r) => binary(l, r, BitAnd),
+        BitOr(l, r) => binary(l, r, BitOr),
+        EQ(l, r) => binary(l, r, EQ),
+        NE(l, r) => binary(l, r, NE),
+        GT(l, r) => binary(l, r, GT),
+        LT(l, r) => binary(l, r, LT),
+        GE(l, r) => binary(l, r, GE),
+        LE(l, r) => binary(l, r, LE),
+        And(l, r) => binary(l, r, And),
+        Or(l, r) => binary(l, r, Or),
+        Default(l, r) => binary(l, r, Default),
+        IfElse(c, b, e) => {
+            let c = f.fold_expr(c);
+            let b = f.fold_expr(b);
+            IfElse(c, b, f.fold_expr(e))
+        }
+        While(c, body, label) => {
+            let label = label.map(|label| f.fold_ident(label));
+            let c = f.fold_expr(c);
+            While(c, f.fold_expr(body), label)
+        }
+        Generator(c, body, label) => {
+            let label = label.map(|label| f.fold_ident(label));
+            let c = f.fold_expr(c);
+            Generator(c, f.fold_expr(body), label)
+        }
+        ForEach(p, iterable, body, label) => {
+            let label = label.map(|label| f.fold_ident(label));
+            let p = f.fold_pattern(p);
+            let iterable = f.fold_expr(iterable);
+            ForEach(p, iterable, f.fold_expr(body), label)
+        }
+        ForEachGenerator(p, iterable, body, label) => {
+            let label = label.map(|label| f.fold_ident(label));
+            let p = f.fold_pattern(p);
+            let iterable = f.fold_expr(iterable);
+            ForEachGenerator(p, iterable, f.fold_expr(body), label)
+        }
+        Assign(p, value) => {
+            let p = f.fold_pattern(p);
+            Assign(p, f.fold_expr(value))
+        }
+        Catch(body, p, handler) => {
+            let body = f.fold_expr(body);
+            let p = f.fold_pattern(p);
+            Catch(body, p, f.fold_expr(handler))
+        }
+        Range(l, r, step) => {
+            let l = f.fold_expr(l);
+            let r = f.fold_expr(r);
+            Range(l, r, step.map(|step| f.fold_expr(step)))
+        }
+        RangeInclusive(l, r, step) => {
+            let l = f.fold_expr(l);
+            let r = f.fold_expr(r);
+            RangeInclusive(l, r, step.map(|step| f.fold_expr(step)))
+        }
+    }
+}
+
+pub fn fold_pattern<F: Fold + ?Sized>(f: &mut F, p: Pattern) -> Pattern {
+    match p {
+        Pattern::Var(var) => Pattern::Var(fold_var_def(f, var)),
+        p @ (Pattern::Wildcard(_) | Pattern::LitNum(..) | Pattern::LitStr(..)) => p,
+        Pattern::Tuple(items, range) => Pattern::Tuple(fold_patterns(f, items), range),
+        Pattern::Array(items, range) => Pattern::Array(fold_patterns(f, items), range),
+        Pattern::Record(name, fields, range) => {
+            let name = name.map(|name| f.fold_ident(name));
+            let fields = fields
+                .into_iter()
+                .map(|FieldPattern { name, pattern }| FieldPattern {
+                    name: f.fold_ident(name),
+                    pattern: pattern.map(|p| f.fold_pattern(p)),
+                })
+                .collect();
+            Pattern::Record(name, fields, range)
+        }
+        Pattern::Rest(name, range) => Pattern::Rest(name.map(|name| f.fold_ident(name)), range),
+        Pattern::Variant(name, items, range) => {
+            let name = f.fold_ident(name);
+            Pattern::Variant(name, fold_patterns(f, items), range)
+        }
+    }
+}
+
+pub fn fold_type_expr<F: Fold + ?Sized>(f: &mut F, t: TypeExpr) -> TypeExpr {
+    match t {
+        TypeExpr::Name(name) => TypeExpr::Name(f.fold_ident(name)),
+        t @ TypeExpr::Unit(_) => t,
+        TypeExpr::Tuple(items, range) => TypeExpr::Tuple(fold_type_exprs(f, items), range),
+        TypeExpr::Union(items) => TypeExpr::Union(fold_type_exprs(f, items)),
+        TypeExpr::Array(t, range) => TypeExpr::Array(Box::new(f.fold_type_expr(*t)), range),
+        TypeExpr::Optional(t, range) => TypeExpr::Optional(Box::new(f.fold_type_expr(*t)), range),
+        TypeExpr::Thrown(t, range) => TypeExpr::Thrown(Box::new(f.fold_type_expr(*t)), range),
+        TypeExpr::Func(func, range) => {
+            let (params, ret) = *func;
+            let params = fold_type_exprs(f, params);
+            TypeExpr::Func(Box::new((params, f.fold_type_expr(ret))), range)
+        }
+        TypeExpr::Generic(name, args, range) => {
+            let name = f.fold_ident(name);
+            TypeExpr::Generic(name, fold_type_exprs(f, args), range)
+        }
+    }
+}
+
+pub fn fold_func<F: Fold + ?Sized>(
+    f: &mut F,
+    Func {
+        params,
+        ret_type,
+        body,
+        ret_doc,
+    }: Func,
+) -> Func {
+    let params = params
+        .into_iter()
+        .map(|Param { pattern, doc }| Param {
+            pattern: f.fold_pattern(pattern),
+            doc,
+        })
+        .collect();
+    Func {
+        params,
+        ret_type: ret_type.map(|t| f.fold_type_expr(t)),
+        body: f.fold_expr(body),
+        ret_doc,
+    }
+}
+
+pub fn fold_arg<F: Fold + ?Sized>(f: &mut F, arg: Arg) -> Arg {
+    match arg {
+        Arg::Positional(e) => Arg::Positional(f.fold_expr(e)),
+        Arg::Named(name, e) => {
+            let name = f.fold_ident(name);
+            Arg::Named(name, f.fold_expr(e))
+        }
+        Arg::Spread(e, range) => Arg::Spread(f.fold_expr(e), range),
+    }
+}
+
+pub fn fold_attribute<F: Fold + ?Sized>(
+    f: &mut F,
+    Attribute { name, args, range }: Attribute,
+) -> Attribute {
+    let name = f.fold_ident(name);
+    Attribute {
+        name,
+        args: fold_exprs(f, args),
+        range,
+    }
+}
+
+pub fn fold_arm<F: Fold + ?Sized>(
+    f: &mut F,
+    Arm {
+        pattern,
+        guard,
+        body,
+    }: Arm,
+) -> Arm {
+    Arm {
+        pattern: f.fold_pattern(pattern),
+        guard: guard.map(|guard| f.fold_expr(guard)),
+        body: f.fold_expr(body),
+    }
+}
+
+fn fold_var_def<F: Fold + ?Sized>(f: &mut F, VarDef { name, ty }: VarDef) -> VarDef {
+    VarDef {
+        name: f.fold_ident(name),
+        ty: ty.map(|t| f.fold_type_expr(t)),
+    }
+}
+
+fn fold_box<F: Fold + ?Sized>(f: &mut F, mut e: Box<Expr>) -> Box<Expr> {
+    *e = f.fold_expr(*e);
+    e
+}
+
+fn fold_exprs<F: Fold + ?Sized>(f: &mut F, items: Vec<Expr>) -> Vec<Expr> {
+    items.into_iter().map(|e| f.fold_expr(e)).collect()
+}
+
+fn fold_patterns<F: Fold + ?Sized>(f: &mut F, items: Vec<Pattern>) -> Vec<Pattern> {
+    items.into_iter().map(|p| f.fold_pattern(p)).collect()
+}
+
+fn fold_idents<F: Fold + ?Sized>(f: &mut F, idents: Vec<Ident>) -> Vec<Ident> {
+    idents.into_iter().map(|i| f.fold_ident(i)).collect()
+}
+
+fn fold_type_exprs<F: Fold + ?Sized>(f: &mut F, items: Vec<TypeExpr>) -> Vec<TypeExpr> {
+    items.into_iter().map(|t| f.fold_type_expr(t)).collect()
+}